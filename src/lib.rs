@@ -1,913 +1,4913 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::LookupMap;
+use near_sdk::collections::{LookupMap, TreeMap, UnorderedSet, Vector};
+use near_sdk::json_types::{U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, near_bindgen};
+use near_sdk::{
+    assert_one_yocto, env, near_bindgen, AccountId, Balance, BorshStorageKey, Gas, PanicOnDefault,
+    Promise,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Bound;
+
+use chrono::{TimeZone, Utc};
+use sha256::digest_bytes;
+
+const DEFAULT_HISTORY_LIMIT: u64 = 50;
+const DEFAULT_PAGE_LIMIT: u64 = 50;
+const NANOS_PER_DAY: u64 = 86_400_000_000_000;
+/// How long before its expiry a `Green` file reads as `Amber`, until admins
+/// change it.
+const DEFAULT_EXPIRY_WARNING_PERIOD: u64 = 30 * NANOS_PER_DAY;
+const EVENT_STANDARD: &str = "kwiktrust";
+const EVENT_VERSION: &str = "1.0.0";
+/// Layout of the contract state. v1 was the untagged layout `migrate` reads.
+const STATE_VERSION: u16 = 2;
+/// Prefix of the v1 `projects` map.
+const LEGACY_PROJECTS_PREFIX: &[u8] = b"r";
+/// Raw storage key of the staged upgrade's wasm, kept out of the state root.
+const STAGED_CODE_KEY: &[u8] = b"staged_code";
+/// Gas attached to the `migrate_state` call that follows an upgrade.
+const MIGRATE_GAS: Gas = Gas(100_000_000_000_000);
+/// A receipt may log at most 16 KiB in total.
+const MAX_LOG_BYTES: usize = 16 * 1024;
+/// Largest event `data` entry when its ids are hashes, account ids or
+/// validator ids and its values statuses or hashes, all at their longest.
+const MAX_EVENT_DATA_BYTES: usize = 720;
+/// Calls that announce an event per entity they touch collect them into one
+/// log per event name, see `collect_events`, and refuse to touch more
+/// entities than fit in the log budget. 1 KiB is kept for the envelopes and
+/// any contract events.
+const MAX_EVENTS_PER_CALL: usize = (MAX_LOG_BYTES - 1024) / MAX_EVENT_DATA_BYTES;
 
-use sha256::digest;
-use chrono::Utc;
+/// Prefixes of every collection. Per-project collections are namespaced by
+/// the project hash.
+#[derive(BorshStorageKey, BorshSerialize)]
+enum StorageKey {
+    Folders,
+    FolderNames,
+    Users,
+    Files,
+    Validators,
+    Suppliers,
+    ProjectFolderIds { project_hash: String },
+    ProjectUserIds { project_hash: String },
+    ProjectFileIds { project_hash: String },
+    ProjectSupplierIds { project_hash: String },
+    ProjectBatchIds { project_hash: String },
+    ProjectAuditLog { project_hash: String },
+    Projects,
+    Admins,
+    StorageAccounts,
+    FolderFiles,
+    FolderFileIds { folder_key: String },
+    FileProjects,
+    FileProjectIds { file_hash: String },
+    Batches,
+    BatchProjects,
+    BatchProjectIds { merkle_root: String },
+    FolderBatches,
+    FolderBatchIds { folder_key: String },
+    ProjectExpiringFileIds { project_hash: String },
+}
 
 #[near_bindgen]
 //#[derive(Default, Clone, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
 //#[serde(crate = "near_sdk::serde")]
-#[derive(BorshDeserialize, BorshSerialize)]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
-    projects: LookupMap<String, Project>,
+    version: u16,
+    owner_id: String,
+    // The owner is always one of the admins.
+    admins: UnorderedSet<String>,
+    approval_threshold: u32,
+    staged_upgrade: Option<StagedUpgrade>,
+    pending_changes: Vec<PendingChange>,
+    paused: bool,
+    // Nanoseconds before expiry that a `Green` file reads as `Amber`.
+    expiry_warning_period: u64,
+    // NEP-145 balances. Every project write is paid for by its caller.
+    storage_accounts: LookupMap<String, StorageAccount>,
+    account_storage_bytes: u64,
+    projects: LookupMap<String, VersionedProject>,
+    sequence: u64,
+    // Entities are keyed by `entity_key(project_hash, id)`.
+    // Top-level folders and sub folders at any depth.
+    folders: LookupMap<String, VersionedFolder>,
+    // `folder_name_key` to folder hash, for sibling-name uniqueness.
+    folder_names: LookupMap<String, String>,
+    // Hashes of the files in each folder, keyed like the folder.
+    folder_files: LookupMap<String, UnorderedSet<String>>,
+    // Hashes of the projects each file hash has been added to.
+    file_projects: LookupMap<String, UnorderedSet<String>>,
+    batches: LookupMap<String, VersionedBatch>,
+    // Hashes of the projects each Merkle root has been anchored in.
+    batch_projects: LookupMap<String, UnorderedSet<String>>,
+    // Merkle roots of the batches in each folder, keyed like the folder.
+    folder_batches: LookupMap<String, UnorderedSet<String>>,
+    users: LookupMap<String, VersionedUser>,
+    files: LookupMap<String, VersionedFile>,
+    validators: LookupMap<String, VersionedValidator>,
+    suppliers: LookupMap<String, VersionedSupplier>,
 }
 
-impl Default for Contract {
-    fn default() -> Self {
-        Self {
-            projects: LookupMap::new(b"r".to_vec()),
+impl Contract {
+    fn create(owner_id: String, admins: Vec<String>, approval_threshold: u32) -> Self {
+        let mut contract = Self {
+            version: STATE_VERSION,
+            owner_id: owner_id.to_string(),
+            admins: UnorderedSet::new(StorageKey::Admins),
+            approval_threshold,
+            staged_upgrade: None,
+            pending_changes: Vec::new(),
+            paused: false,
+            expiry_warning_period: DEFAULT_EXPIRY_WARNING_PERIOD,
+            storage_accounts: LookupMap::new(StorageKey::StorageAccounts),
+            account_storage_bytes: 0,
+            projects: LookupMap::new(StorageKey::Projects),
+            sequence: 0,
+            folders: LookupMap::new(StorageKey::Folders),
+            folder_names: LookupMap::new(StorageKey::FolderNames),
+            folder_files: LookupMap::new(StorageKey::FolderFiles),
+            file_projects: LookupMap::new(StorageKey::FileProjects),
+            batches: LookupMap::new(StorageKey::Batches),
+            batch_projects: LookupMap::new(StorageKey::BatchProjects),
+            folder_batches: LookupMap::new(StorageKey::FolderBatches),
+            users: LookupMap::new(StorageKey::Users),
+            files: LookupMap::new(StorageKey::Files),
+            validators: LookupMap::new(StorageKey::Validators),
+            suppliers: LookupMap::new(StorageKey::Suppliers),
+        };
+
+        contract.admins.insert(&owner_id);
+        for admin in admins {
+            contract.admins.insert(&admin);
+        }
+
+        if approval_threshold == 0 || approval_threshold as u64 > contract.admins.len() {
+            env::panic_str("Approval threshold must be between 1 and the number of admins");
         }
+
+        contract.measure_account_storage();
+
+        contract
+    }
+
+    /// Measures the bytes a storage account takes with the longest possible
+    /// account id, which is what registering costs.
+    fn measure_account_storage(&mut self) {
+        let initial_storage = env::storage_usage();
+        let account_id = "a".repeat(64);
+
+        self.storage_accounts
+            .insert(&account_id, &StorageAccount::default());
+        self.account_storage_bytes = env::storage_usage() - initial_storage;
+        self.storage_accounts.remove(&account_id);
     }
 }
 
-#[near_bindgen]
 impl Contract {
-    pub fn add_project(
-        &mut self,
-        project_name: String,
-        logo: String,
-        created_by: String,
-    ) -> ProjectReturnMessage {
-        let s_slice: &str = &project_name[..];
-        let val = format!("{}{}", &logo, s_slice);
-        let _hash = digest(val);
+    /// Reserves the next sequence number for a write and anchors it to the
+    /// current block. Only call this once the write is going to happen.
+    fn next_anchor(&mut self) -> Anchor {
+        self.sequence += 1;
+        Anchor::new(self.sequence)
+    }
 
-        let transaction_hash = "Transaction Hash".to_owned();
+    /// Charges `account_id` for the bytes written since `initial_storage`,
+    /// or credits it for the bytes freed. Panics, reverting the whole call,
+    /// if the account's deposit doesn't cover its usage.
+    fn settle_storage(&mut self, account_id: &str, initial_storage: u64) {
+        let final_storage = env::storage_usage();
+        let mut account = self
+            .storage_accounts
+            .get(&account_id.to_owned())
+            .unwrap_or_else(|| {
+                env::panic_str(&format!(
+                    "{} is not registered, call storage_deposit first",
+                    account_id
+                ))
+            });
 
-        match self.projects.get(&_hash) {
-            Some(p) => {
-                return ProjectReturnMessage {
-                    result: 409,
-                    message: "Project already exists".to_owned(),
-                    transaction_hash: transaction_hash,
-                    hash: _hash,
-                };
-            }
-            None => {
-                self.projects.insert(
-                    &_hash,
-                    &Project::create_project(
-                        created_by,
-                        _hash.to_string(),
-                        transaction_hash.to_string(),
-                    ),
-                );
+        if final_storage >= initial_storage {
+            account.used_bytes += final_storage - initial_storage;
+        } else {
+            account.used_bytes = account
+                .used_bytes
+                .saturating_sub(initial_storage - final_storage);
+        }
 
-                return ProjectReturnMessage {
-                    result: 200,
-                    message: "Project added successfully".to_owned(),
-                    hash: _hash,
-                    transaction_hash: transaction_hash,
-                };
+        if account.storage_cost() > account.deposit {
+            env::panic_str(&format!(
+                "Insufficient storage balance, {} more yoctoNEAR needed",
+                account.storage_cost() - account.deposit
+            ));
+        }
+
+        self.storage_accounts
+            .insert(&account_id.to_owned(), &account);
+    }
+
+    /// Credits `account_id` for the bytes freed since `initial_storage`
+    /// from data it paid for, and returns how many that was. Nobody is
+    /// credited for data the contract paid for, like migrated v1 data.
+    fn refund_storage(&mut self, account_id: Option<&str>, initial_storage: u64) -> u64 {
+        let freed = initial_storage.saturating_sub(env::storage_usage());
+
+        if let Some(account_id) = account_id.map(str::to_owned) {
+            if let Some(mut account) = self.storage_accounts.get(&account_id) {
+                account.used_bytes = account.used_bytes.saturating_sub(freed);
+                self.storage_accounts.insert(&account_id, &account);
             }
         }
+
+        freed
     }
 
-    pub fn add_folder(
-        &mut self,
-        project_hash: String,
-        project_id: String,
-        folder_name: String,
-    ) -> ReturnMessage {
-        let s_slice: &str = &folder_name[..];
-        let val = format!("{}{}", &project_id, s_slice);
-        let _hash = digest(val);
+    fn index_file(&mut self, project_hash: &str, folder_id: &str, file_hash: &str) {
+        let folder_key = entity_key(project_hash, folder_id);
+        let mut file_ids = self.folder_files.get(&folder_key).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::FolderFileIds {
+                folder_key: folder_key.to_string(),
+            })
+        });
 
-        let transaction_hash = "Transaction Hash".to_owned();
+        file_ids.insert(&file_hash.to_owned());
+        self.folder_files.insert(&folder_key, &file_ids);
 
-        match self.projects.get(&project_hash) {
-            Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
+        let file_hash = file_hash.to_owned();
+        let mut project_ids = self.file_projects.get(&file_hash).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::FileProjectIds {
+                file_hash: file_hash.to_string(),
+            })
+        });
 
-                match p.folders.iter().position(|r| r.folder_name == folder_name) {
-                    Some(_index) => {
-                        return ReturnMessage {
-                            result: 409,
-                            message: "Folder with the same name already exists".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                    None => {
-                        p.folders.push(Folder::create_folder(
-                            _hash,
-                            project_id,
-                            folder_name,
-                            transaction_hash.to_string(),
-                        ));
+        project_ids.insert(&project_hash.to_owned());
+        self.file_projects.insert(&file_hash, &project_ids);
+    }
 
-                        self.projects.insert(&project_hash, &p);
+    fn folder_file_ids(&self, project_hash: &str, folder_id: &str) -> Vec<String> {
+        self.folder_files
+            .get(&entity_key(project_hash, folder_id))
+            .map(|r| r.to_vec())
+            .unwrap_or_default()
+    }
 
-                        return ReturnMessage {
-                            result: 200,
-                            message: "Folder added successfully".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                }
-            }
-            None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
-            }
+    /// Why a file can't be put in `folder_id` by `user_id`, if it can't. The
+    /// folder must be in the project and the uploader a user whose access
+    /// hasn't been revoked and whose role may add files.
+    fn check_file_placement(
+        &self,
+        project_hash: &str,
+        folder_id: &str,
+        user_id: &str,
+    ) -> Option<ReturnMessage> {
+        if !self
+            .folders
+            .contains_key(&entity_key(project_hash, folder_id))
+        {
+            return Some(ReturnMessage::folder_not_found());
+        }
+
+        match self
+            .users
+            .get(&entity_key(project_hash, user_id))
+            .map(User::from)
+        {
+            Some(user) if user.is_revoked => Some(ReturnMessage {
+                code: ResultCode::UserRevoked,
+                message: "Uploader's access has been revoked".to_owned(),
+                anchor: None,
+            }),
+            Some(user) if !user.role.has_permission(Permission::AddFiles) => Some(ReturnMessage {
+                code: ResultCode::Unauthorized,
+                message: "Uploader is not allowed to add files".to_owned(),
+                anchor: None,
+            }),
+            Some(_) => None,
+            None => Some(ReturnMessage {
+                code: ResultCode::UserNotFound,
+                message: "Uploader is not a user of this project".to_owned(),
+                anchor: None,
+            }),
         }
     }
 
-    pub fn add_sub_folder(
+    /// `add_file` and `add_file_commitment`, which only differ in what
+    /// `file_hash` holds.
+    #[allow(clippy::too_many_arguments)]
+    fn insert_file(
         &mut self,
         project_hash: String,
-        project_id: String,
+        file_hash: String,
+        title: String,
+        user_id: String,
         folder_id: String,
-        sub_folder_name: String,
+        expires_at: Option<U64>,
+        committed: bool,
     ) -> ReturnMessage {
-        let s_slice: &str = &sub_folder_name[..];
-        let val = format!("{}{}", &folder_id, s_slice);
-        let _hash = digest(val);
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
 
-        let transaction_hash = "Transaction Hash".to_owned();
+        if self.paused {
+            return ReturnMessage::paused();
+        }
 
-        match self.projects.get(&project_hash) {
+        match self.projects.get(&project_hash).map(Project::from) {
             Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
 
-                match p
-                    .sub_folders
-                    .iter()
-                    .position(|r| r.sub_folder_name == sub_folder_name)
+                if !self.can(&p, &caller, Permission::AddFiles) {
+                    return ReturnMessage::unauthorized();
+                }
+
+                if let Some(error) = self.check_file_placement(&project_hash, &folder_id, &user_id)
                 {
-                    Some(_index) => {
-                        return ReturnMessage {
-                            result: 409,
-                            message: "Sub folder with the same name already exists".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
+                    return error;
+                }
+
+                if let Some(error) = check_expiry(expires_at) {
+                    return error;
+                }
+
+                let key = entity_key(&project_hash, &file_hash);
+
+                match self.files.get(&key).map(File::from) {
+                    Some(_) => ReturnMessage {
+                        code: ResultCode::DuplicateFile,
+                        message: "File already exists".to_owned(),
+                        anchor: None,
+                    },
                     None => {
-                        p.sub_folders.push(SubFolder::create_sub_folder(
-                            _hash,
-                            project_id,
+                        let transaction_type = if committed {
+                            TransactionType::AddFileCommitment
+                        } else {
+                            TransactionType::AddFile
+                        };
+
+                        let anchor = self.next_anchor();
+                        let log_index = p.link(
+                            &file_hash,
+                            UpdateLogs::new(anchor.clone(), transaction_type),
+                        );
+                        let mut file = File::create_file(
+                            file_hash.to_string(),
+                            title,
+                            user_id,
                             folder_id,
-                            sub_folder_name,
-                            transaction_hash.to_string(),
-                        ));
+                            expires_at,
+                            log_index,
+                        );
+                        file.committed = committed;
+                        file.paid_by = Some(caller.to_string());
 
-                        self.projects.insert(&project_hash, &p);
+                        self.index_file(&project_hash, &file.folder_id, &file_hash);
+                        p.index_expiry(&file);
+                        self.files.insert(&key, &file.into());
+                        p.file_ids.insert(&file_hash);
 
-                        return ReturnMessage {
-                            result: 200,
-                            message: "Sub folder added successfully".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "File added successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
                     }
                 }
             }
-            None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
-            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
         }
     }
 
-    pub fn add_user(
+    /// Runs `add` on each of `items` in order for the batch `add_*` methods,
+    /// returning one result per item. Best effort keeps going past failed
+    /// items. An atomic batch panics on the first failure instead, so nothing
+    /// in it is kept. An item is only tried while the receipt can log another
+    /// event and the caller's storage deposit covers another item as big as
+    /// the biggest so far. Otherwise it fails with `TooManyItems` or
+    /// `InsufficientStorage`, rather than a panic undoing the items before it.
+    fn apply_batch<T, R: From<ReturnMessage> + AsRef<ReturnMessage>>(
         &mut self,
-        project_hash: String,
-        user_name: String,
-        user_id: String,
-    ) -> ReturnMessage {
-        let transaction_hash = "Transaction Hash".to_owned();
-
-        match self.projects.get(&project_hash) {
-            Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
+        items: Vec<T>,
+        atomic: bool,
+        mut add: impl FnMut(&mut Self, usize, T) -> R,
+    ) -> Vec<R> {
+        if items.len() > MAX_EVENTS_PER_CALL {
+            env::panic_str(&format!(
+                "A batch can hold at most {} items",
+                MAX_EVENTS_PER_CALL
+            ));
+        }
 
-                match p.users.iter().position(|r| r.user_id == user_id) {
-                    Some(_index) => {
-                        return ReturnMessage {
-                            result: 409,
-                            message: "User already exists".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                    None => {
-                        p.users.push(User::create_user(
-                            user_name,
-                            user_id,
-                            transaction_hash.to_string(),
-                        ));
+        let caller = env::predecessor_account_id().to_string();
+        let mut largest_item: u64 = 0;
+        let mut results = Vec::with_capacity(items.len());
+        collect_events();
 
-                        self.projects.insert(&project_hash, &p);
+        for (i, item) in items.into_iter().enumerate() {
+            let available = self
+                .storage_accounts
+                .get(&caller)
+                .map_or(0, |r| r.available());
 
-                        return ReturnMessage {
-                            result: 200,
-                            message: "User added successfully".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
+            let result: R = if collected_event_bytes() + MAX_EVENT_DATA_BYTES > MAX_LOG_BYTES {
+                ReturnMessage {
+                    code: ResultCode::TooManyItems,
+                    message: "This call can't log any more events".to_owned(),
+                    anchor: None,
                 }
+                .into()
+            } else if available < largest_item as Balance * env::storage_byte_cost() {
+                ReturnMessage {
+                    code: ResultCode::InsufficientStorage,
+                    message: "Storage balance doesn't cover another item, call storage_deposit"
+                        .to_owned(),
+                    anchor: None,
+                }
+                .into()
+            } else {
+                let before = env::storage_usage();
+                let result = add(self, i, item);
+                largest_item = largest_item.max(env::storage_usage().saturating_sub(before));
+                result
+            };
+
+            let message = result.as_ref();
+            if atomic && !message.code.is_success() {
+                env::panic_str(&format!(
+                    "Item {} failed with {:?}: {}",
+                    i, message.code, message.message
+                ));
             }
-            None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
-            }
+
+            results.push(result);
         }
+
+        flush_events();
+        results
     }
 
-    pub fn add_user_access(&mut self, project_hash: String, user_id: String) -> ReturnMessage {
-        let transaction_hash = "Transaction Hash".to_owned();
+    fn file_view(&self, file: File) -> FileView {
+        FileView {
+            effective_status: file
+                .effective_status(env::block_timestamp(), self.expiry_warning_period),
+            file,
+        }
+    }
 
-        match self.projects.get(&project_hash) {
-            Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
+    /// Every folder below `folder_hash`, parents before their children.
+    fn descendants(&self, project: &Project, folder_hash: &str) -> Vec<Folder> {
+        let mut children: HashMap<String, Vec<Folder>> = HashMap::new();
+        for folder in project.folder_ids.iter().filter_map(|id| {
+            self.folders
+                .get(&entity_key(&project.project_hash, &id))
+                .map(Folder::from)
+        }) {
+            if let Some(parent_id) = folder.parent_id.clone() {
+                children.entry(parent_id).or_default().push(folder);
+            }
+        }
 
-                match p.users.iter().position(|r| r.user_id == user_id) {
-                    Some(_index) => {
-                        if p.users[_index].is_revoked == true {
-                            p.users[_index].is_revoked = false;
+        let mut descendants = children.remove(folder_hash).unwrap_or_default();
+        let mut i = 0;
+        while i < descendants.len() {
+            let grandchildren = children
+                .remove(&descendants[i].folder_hash)
+                .unwrap_or_default();
+            descendants.extend(grandchildren);
+            i += 1;
+        }
 
-                            self.projects.insert(&project_hash, &p);
-                        } else {
-                            return ReturnMessage {
-                                result: 200,
-                                message: "User's access is already enabled".to_owned(),
-                                transaction_hash: transaction_hash,
-                            };
-                        }
+        descendants
+    }
 
-                        return ReturnMessage {
-                            result: 200,
-                            message: "User's access added successfully".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                    None => {
-                        return ReturnMessage {
-                            result: 404,
-                            message: "User not found".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                }
-            }
-            None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
+    /// Whether `folder_hash` is `ancestor_hash` or sits somewhere below it.
+    fn is_within(&self, project_hash: &str, folder_hash: &str, ancestor_hash: &str) -> bool {
+        let mut current = Some(folder_hash.to_owned());
+
+        while let Some(folder_hash) = current {
+            if folder_hash == ancestor_hash {
+                return true;
             }
+
+            current = self
+                .folders
+                .get(&entity_key(project_hash, &folder_hash))
+                .map(Folder::from)
+                .and_then(|r| r.parent_id);
         }
+
+        false
     }
 
-    pub fn remove_user_access(&mut self, project_hash: String, user_id: String) -> ReturnMessage {
-        let transaction_hash = "Transaction Hash".to_owned();
+    /// For the NEP-145 methods, whose results can't carry a `ResultCode`.
+    fn assert_not_paused(&self) {
+        if self.paused {
+            env::panic_str("Contract is paused");
+        }
+    }
 
-        match self.projects.get(&project_hash) {
-            Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
+    /// Records the caller's approval of `change`, and applies it once
+    /// `approval_threshold` current admins have approved it, the same bar as
+    /// an upgrade. Until then it returns `ResultCode::AwaitingApprovals`.
+    fn approve_change(&mut self, change: GovernanceChange) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
 
-                match p.users.iter().position(|r| r.user_id == user_id) {
-                    Some(_index) => {
-                        if p.users[_index].is_revoked == false {
-                            p.users[_index].is_revoked = true;
+        if !self.is_admin(&caller) {
+            return ReturnMessage::not_admin();
+        }
 
-                            self.projects.insert(&project_hash, &p);
-                        } else {
-                            return ReturnMessage {
-                                result: 200,
-                                message: "User's access is already disabled".to_owned(),
-                                transaction_hash: transaction_hash,
-                            };
-                        }
+        if let Some(error) = self.check_change(&change) {
+            return error;
+        }
 
-                        return ReturnMessage {
-                            result: 200,
-                            message: "User's access removed successfully".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                    None => {
-                        return ReturnMessage {
-                            result: 404,
-                            message: "User not found".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                }
-            }
+        let i = match self.pending_changes.iter().position(|r| r.change == change) {
+            Some(i) => i,
             None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
+                self.pending_changes.push(PendingChange {
+                    change: change.clone(),
+                    approvals: Vec::new(),
+                });
+                self.pending_changes.len() - 1
             }
+        };
+
+        if self.pending_changes[i].approvals.contains(&caller) {
+            return ReturnMessage {
+                code: ResultCode::Unchanged,
+                message: "Change is already approved by this admin".to_owned(),
+                anchor: None,
+            };
         }
-    }
 
-    pub fn add_file(
-        &mut self,
-        project_hash: String,
-        file_hash: String,
-        title: String,
-        user_id: String,
-        folder_id: String,
-        expiry_date: String,
-    ) -> ReturnMessage {
-        let transaction_hash = "Transaction Hash".to_owned();
+        let anchor = self.next_anchor();
+        self.pending_changes[i].approvals.push(caller);
 
-        match self.projects.get(&project_hash) {
-            Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
+        let approvals = self.pending_changes[i]
+            .approvals
+            .iter()
+            .filter(|r| self.is_admin(r))
+            .count() as u32;
 
-                match p.files.iter().position(|r| r.file_hash == file_hash) {
-                    Some(_index) => {
-                        return ReturnMessage {
-                            result: 409,
-                            message: "File already exists".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                    None => {
-                        p.files.push(File::create_file(
-                            file_hash,
-                            title,
-                            user_id,
-                            folder_id,
-                            expiry_date,
-                            transaction_hash.to_string(),
-                        ));
+        if approvals < self.approval_threshold {
+            emit_contract_event("governance_change_approved", &anchor);
+            return ReturnMessage {
+                code: ResultCode::AwaitingApprovals,
+                message: format!(
+                    "Change has {} of {} approvals",
+                    approvals, self.approval_threshold
+                ),
+                anchor: Some(anchor),
+            };
+        }
 
-                        self.projects.insert(&project_hash, &p);
+        self.pending_changes.remove(i);
+        let (event, message) = match change {
+            GovernanceChange::AddAdmin { account_id } => {
+                self.admins.insert(&account_id);
+                ("admin_added", "Admin added successfully")
+            }
+            GovernanceChange::RemoveAdmin { account_id } => {
+                self.admins.remove(&account_id);
+                ("admin_removed", "Admin removed successfully")
+            }
+            GovernanceChange::SetApprovalThreshold { approval_threshold } => {
+                self.approval_threshold = approval_threshold;
+                (
+                    "approval_threshold_changed",
+                    "Approval threshold updated successfully",
+                )
+            }
+        };
+        emit_contract_event(event, &anchor);
 
-                        return ReturnMessage {
-                            result: 200,
-                            message: "File added successfully".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                }
+        // Drop changes this one made moot, like adding an admin twice.
+        let pending_changes = std::mem::take(&mut self.pending_changes);
+        self.pending_changes = pending_changes
+            .into_iter()
+            .filter(|r| self.check_change(&r.change).is_none())
+            .collect();
+
+        ReturnMessage {
+            code: ResultCode::Ok,
+            message: message.to_owned(),
+            anchor: Some(anchor),
+        }
+    }
+
+    /// Why `change` can't be applied to the current admins, if it can't.
+    fn check_change(&self, change: &GovernanceChange) -> Option<ReturnMessage> {
+        match change {
+            GovernanceChange::AddAdmin { account_id } if self.is_admin(account_id) => {
+                Some(ReturnMessage {
+                    code: ResultCode::DuplicateAdmin,
+                    message: "Account is already an admin".to_owned(),
+                    anchor: None,
+                })
             }
-            None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
+            GovernanceChange::RemoveAdmin { account_id } if !self.is_admin(account_id) => {
+                Some(ReturnMessage {
+                    code: ResultCode::AdminNotFound,
+                    message: "Admin not found".to_owned(),
+                    anchor: None,
+                })
+            }
+            GovernanceChange::RemoveAdmin { account_id } if *account_id == self.owner_id => {
+                Some(ReturnMessage {
+                    code: ResultCode::Unauthorized,
+                    message: "The owner can't be removed as an admin".to_owned(),
+                    anchor: None,
+                })
+            }
+            GovernanceChange::RemoveAdmin { .. }
+                if self.approval_threshold as u64 > self.admins.len() - 1 =>
+            {
+                Some(ReturnMessage {
+                    code: ResultCode::InvalidThreshold,
+                    message: "Approval threshold would exceed the number of admins".to_owned(),
+                    anchor: None,
+                })
+            }
+            GovernanceChange::SetApprovalThreshold { approval_threshold }
+                if *approval_threshold == 0 || *approval_threshold as u64 > self.admins.len() =>
+            {
+                Some(ReturnMessage {
+                    code: ResultCode::InvalidThreshold,
+                    message: "Approval threshold must be between 1 and the number of admins"
+                        .to_owned(),
+                    anchor: None,
+                })
             }
+            GovernanceChange::SetApprovalThreshold { approval_threshold }
+                if *approval_threshold == self.approval_threshold =>
+            {
+                Some(ReturnMessage {
+                    code: ResultCode::Unchanged,
+                    message: "Approval threshold is already set".to_owned(),
+                    anchor: None,
+                })
+            }
+            _ => None,
         }
     }
 
-    pub fn accept_file(&mut self, project_hash: String, file_hash: String) -> ReturnMessage {
-        let transaction_hash = "Transaction Hash".to_owned();
+    fn is_admin(&self, account_id: &str) -> bool {
+        self.admins.contains(&account_id.to_owned())
+    }
 
-        match self.projects.get(&project_hash) {
-            Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
+    /// Role of `account_id` on `project`, `None` if it isn't a user or its
+    /// access has been revoked. The creator always holds `Role::Owner`.
+    fn role_of(&self, project: &Project, account_id: &str) -> Option<Role> {
+        if project.is_owner(account_id) {
+            return Some(Role::Owner);
+        }
 
-                match p.files.iter().position(|r| r.file_hash == file_hash) {
-                    Some(_index) => {
-                        if p.files[_index].file_status != "GREEN" {
-                            p.files[_index].file_status = "GREEN".to_owned();
+        self.users
+            .get(&entity_key(&project.project_hash, account_id))
+            .map(User::from)
+            .filter(|r| !r.is_revoked)
+            .map(|r| r.role)
+    }
 
-                            self.projects.insert(&project_hash, &p);
-                        } else {
-                            return ReturnMessage {
-                                result: 200,
-                                message: "File status already accepted".to_owned(),
-                                transaction_hash: transaction_hash,
-                            };
-                        }
+    fn can(&self, project: &Project, account_id: &str, permission: Permission) -> bool {
+        self.role_of(project, account_id)
+            .is_some_and(|role| role.has_permission(permission))
+    }
 
-                        return ReturnMessage {
-                            result: 200,`
-                            message: "File status accepted successfully".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                    None => {
-                        return ReturnMessage {
-                            result: 404,
-                            message: "File not found".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                }
+    /// Checks `account_id` may move a file from `from` to `to`, see
+    /// `FileStatus::required_permission`.
+    fn check_transition(
+        &self,
+        project: &Project,
+        account_id: &str,
+        from: FileStatus,
+        to: FileStatus,
+    ) -> Option<ReturnMessage> {
+        match from.required_permission(to) {
+            Some(permission) if self.can(project, account_id, permission) => None,
+            Some(_) => Some(ReturnMessage::unauthorized()),
+            None => Some(ReturnMessage::illegal_transition(from, to)),
+        }
+    }
+
+    /// Whether `account_id` may grant, change or revoke a user holding `role`.
+    /// Nobody can hand out or take away ownership, and admins are managed by
+    /// the owner only.
+    fn can_assign_role(&self, project: &Project, account_id: &str, role: Role) -> bool {
+        match role {
+            Role::Owner => false,
+            Role::Admin => project.is_owner(account_id),
+            _ => self.can(project, account_id, Permission::ManageUsers),
+        }
+    }
+
+    /// Moves the v1 projects named in `project_hashes` out of `legacy` and
+    /// into the current layout. Hashes that aren't in `legacy`, or that were
+    /// already migrated, are skipped. Returns how many projects were moved.
+    fn migrate_v1_projects(
+        &mut self,
+        legacy: &mut LookupMap<String, ProjectV1>,
+        project_hashes: Vec<String>,
+    ) -> u64 {
+        let mut migrated = 0;
+
+        for project_hash in project_hashes {
+            if self.projects.contains_key(&project_hash) {
+                continue;
             }
-            None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
+
+            if let Some(v1) = legacy.remove(&project_hash) {
+                self.migrate_v1_project(v1);
+                migrated += 1;
             }
         }
+
+        migrated
     }
 
-    pub fn reject_file(&mut self, project_hash: String, file_hash: String) -> ReturnMessage {
-        let transaction_hash = "Transaction Hash".to_owned();
+    /// v1 entities carried a single log entry with a wall-clock time stamp
+    /// and no block. Each one is re-recorded as a `Migrate` entry anchored to
+    /// this migration, keeping the v1 entry as its `old_value`. Migrated
+    /// entries aren't announced as events: a project can easily have more
+    /// entities than a receipt may log.
+    fn migrate_v1_project(&mut self, v1: ProjectV1) {
+        let anchor = self.next_anchor();
+        let project_hash = v1.project_hash;
+        let mut p = Project::new(v1.created_by, project_hash.to_string());
 
-        match self.projects.get(&project_hash) {
-            Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
+        let log_index = p.append(&project_hash, v1.update_logs.migrate(anchor.clone()));
+        p.update_logs.push(log_index);
 
-                match p.files.iter().position(|r| r.file_hash == file_hash) {
-                    Some(_index) => {
-                        if p.files[_index].file_status != "RED" {
-                            p.files[_index].file_status = "RED".to_owned();
+        for r in v1.folders {
+            let log_index = p.append(&r.folder_hash, r.update_logs.migrate(anchor.clone()));
+            self.folder_names.insert(
+                &folder_name_key(&project_hash, None, &r.folder_name),
+                &r.folder_hash,
+            );
+            self.folders.insert(
+                &entity_key(&project_hash, &r.folder_hash),
+                &Folder::create_folder(
+                    r.folder_hash.to_string(),
+                    r.project_id,
+                    None,
+                    r.folder_name,
+                    log_index,
+                )
+                .into(),
+            );
+            p.folder_ids.insert(&r.folder_hash);
+        }
 
-                            self.projects.insert(&project_hash, &p);
-                        } else {
-                            return ReturnMessage {
-                                result: 200,
-                                message: "File status already rejected".to_owned(),
-                                transaction_hash: transaction_hash,
-                            };
-                        }
+        // v1 never checked that a sub folder's folder exists. The tree view
+        // shows those under the root.
+        for r in v1.sub_folders {
+            let log_index = p.append(&r.sub_folder_hash, r.update_logs.migrate(anchor.clone()));
+            self.folder_names.insert(
+                &folder_name_key(&project_hash, Some(&r.folder_id), &r.sub_folder_name),
+                &r.sub_folder_hash,
+            );
+            self.folders.insert(
+                &entity_key(&project_hash, &r.sub_folder_hash),
+                &Folder::create_folder(
+                    r.sub_folder_hash.to_string(),
+                    r.project_id,
+                    Some(r.folder_id),
+                    r.sub_folder_name,
+                    log_index,
+                )
+                .into(),
+            );
+            p.folder_ids.insert(&r.sub_folder_hash);
+        }
 
-                        return ReturnMessage {
-                            result: 200,
-                            message: "File status accepted successfully".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                    None => {
-                        return ReturnMessage {
-                            result: 404,
-                            message: "File not found".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
+        // v1 had no roles. Everyone added was an editor in all but name.
+        for r in v1.users {
+            let role = if p.is_owner(&r.user_id) {
+                Role::Owner
+            } else {
+                Role::Editor
+            };
+            let log_index = p.append(&r.user_id, r.update_logs.migrate(anchor.clone()));
+            let mut user = User::create_user(r.user_name, r.user_id.to_string(), role, log_index);
+            user.is_revoked = r.is_revoked && role != Role::Owner;
+
+            self.users
+                .insert(&entity_key(&project_hash, &r.user_id), &user.into());
+            p.user_ids.insert(&r.user_id);
+        }
+
+        // v1 didn't add the creator as a user.
+        if !p.user_ids.contains(&p.created_by) {
+            let created_by = p.created_by.to_string();
+            let log_index = p.append(
+                &created_by,
+                UpdateLogs::new(anchor.clone(), TransactionType::AddUser),
+            );
+            self.users.insert(
+                &entity_key(&project_hash, &created_by),
+                &User::create_user(
+                    created_by.to_string(),
+                    created_by.to_string(),
+                    Role::Owner,
+                    log_index,
+                )
+                .into(),
+            );
+            p.user_ids.insert(&created_by);
+        }
+
+        for r in v1.files {
+            let log_index = p.append(&r.file_hash, r.update_logs.migrate(anchor.clone()));
+            let mut file = File::create_file(
+                r.file_hash.to_string(),
+                r.file_title,
+                r.user_id,
+                r.folder_id,
+                parse_v1_expiry(&r.expiry_date),
+                log_index,
+            );
+            file.file_status = r.file_status.parse().unwrap_or(FileStatus::Red);
+
+            for v in r.validators {
+                let entity_id = validator_entity_id(&r.file_hash, &v.validator_id);
+                let key = entity_key(&project_hash, &entity_id);
+
+                if self.validators.contains_key(&key) {
+                    continue;
                 }
+
+                let log_index = p.append(&entity_id, v.update_logs.migrate(anchor.clone()));
+                let mut validator = Validator::create_validator(
+                    v.validator_id.to_string(),
+                    v.validator_ip,
+                    v.validator_email,
+                    v.validator_organization,
+                    v.can_sign,
+                    log_index,
+                );
+                validator.is_revoked = v.is_revoked;
+                validator.file_status = v.file_status.parse().ok();
+
+                self.validators.insert(&key, &validator.into());
+                file.validators.push(v.validator_id);
             }
+
+            self.index_file(&project_hash, &file.folder_id, &r.file_hash);
+            p.index_expiry(&file);
+            self.files
+                .insert(&entity_key(&project_hash, &r.file_hash), &file.into());
+            p.file_ids.insert(&r.file_hash);
+        }
+
+        for r in v1.suppliers {
+            let log_index = p.append(&r.supplier_email, r.update_logs.migrate(anchor.clone()));
+            self.suppliers.insert(
+                &entity_key(&project_hash, &r.supplier_email),
+                &Supplier::create_supplier(
+                    r.category,
+                    r.contact_name,
+                    r.supplier_id,
+                    r.supplier_email.to_string(),
+                    r.company_name,
+                    r.company_website,
+                    r.requested_documents,
+                    log_index,
+                )
+                .into(),
+            );
+            p.supplier_ids.insert(&r.supplier_email);
+        }
+
+        self.projects.insert(&project_hash, &p.into());
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// `owner_id` is an admin that can't be removed. Upgrades and changes to
+    /// the admins or the approval threshold need `approval_threshold` admin
+    /// approvals.
+    #[init]
+    pub fn new(owner_id: String, admins: Vec<String>, approval_threshold: u32) -> Self {
+        Self::create(owner_id, admins, approval_threshold)
+    }
+
+    /// Creates a project owned by the calling account. Its hash is
+    /// `project_id(caller, project_name, logo)`.
+    pub fn add_project(&mut self, project_name: String, logo: String) -> ProjectReturnMessage {
+        let created_by = env::predecessor_account_id().to_string();
+        let _hash = project_id(&created_by, &project_name, &logo);
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ProjectReturnMessage {
+                code: ResultCode::ContractPaused,
+                message: "Contract is paused".to_owned(),
+                anchor: None,
+                hash: _hash,
+            };
+        }
+
+        match self.projects.get(&_hash).map(Project::from) {
+            Some(_) => ProjectReturnMessage {
+                code: ResultCode::DuplicateProject,
+                message: "Project already exists".to_owned(),
+                anchor: None,
+                hash: _hash,
+            },
             None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
+                let anchor = self.next_anchor();
+                let mut p = Project::create_project(
+                    created_by.to_string(),
+                    _hash.to_string(),
+                    anchor.clone(),
+                );
+
+                let log_index = p.link(
+                    &created_by,
+                    UpdateLogs::new(anchor.clone(), TransactionType::AddUser),
+                );
+                self.users.insert(
+                    &entity_key(&_hash, &created_by),
+                    &User::create_user(
+                        created_by.to_string(),
+                        created_by.to_string(),
+                        Role::Owner,
+                        log_index,
+                    )
+                    .into(),
+                );
+                p.user_ids.insert(&created_by);
+
+                self.projects.insert(&_hash, &p.into());
+                self.settle_storage(&created_by, initial_storage);
+
+                ProjectReturnMessage {
+                    code: ResultCode::Ok,
+                    message: "Project added successfully".to_owned(),
+                    hash: _hash,
+                    anchor: Some(anchor),
+                }
             }
         }
     }
 
-    pub fn update_file(
+    pub fn add_folder(
         &mut self,
         project_hash: String,
-        file_hash: String,
-        update_status: String,
+        project_id: String,
+        folder_name: String,
+    ) -> FolderReturnMessage {
+        let result = self.insert_folder(&project_hash, project_id, None, folder_name);
+        FolderReturnMessage::new(&project_hash, result)
+    }
+
+    /// Adds a folder under `folder_id`, which can be a top-level folder or a
+    /// sub folder at any depth. Names are unique among a folder's children.
+    pub fn add_sub_folder(
+        &mut self,
+        project_hash: String,
+        project_id: String,
+        folder_id: String,
+        sub_folder_name: String,
+    ) -> FolderReturnMessage {
+        let result =
+            self.insert_folder(&project_hash, project_id, Some(folder_id), sub_folder_name);
+        FolderReturnMessage::new(&project_hash, result)
+    }
+
+    /// Adds a folder for `add_folder` and `add_sub_folder`. Its hash comes
+    /// from the write that adds it, see `folder_id`.
+    fn insert_folder(
+        &mut self,
+        project_hash: &str,
+        project_id: String,
+        parent_id: Option<String>,
+        folder_name: String,
     ) -> ReturnMessage {
-        let transaction_hash = "Transaction Hash".to_owned();
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
 
-        match self.projects.get(&project_hash) {
-            Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
+        if self.paused {
+            return ReturnMessage::paused();
+        }
 
-                match p.files.iter().position(|r| r.file_hash == file_hash) {
-                    Some(_index) => {
-                        p.files[_index].file_status = update_status;
+        match self
+            .projects
+            .get(&project_hash.to_owned())
+            .map(Project::from)
+        {
+            Some(mut p) => {
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
 
-                        self.projects.insert(&project_hash, &p);
+                if !self.can(&p, &caller, Permission::ManageFolders) {
+                    return ReturnMessage::unauthorized();
+                }
 
+                if let Some(parent_id) = &parent_id {
+                    if !self
+                        .folders
+                        .contains_key(&entity_key(project_hash, parent_id))
+                    {
                         return ReturnMessage {
-                            result: 200,
-                            message: "File status updated successfully".to_owned(),
-                            transaction_hash: transaction_hash,
+                            code: ResultCode::FolderNotFound,
+                            message: "Parent folder not found".to_owned(),
+                            anchor: None,
                         };
                     }
+                }
+
+                let name_key = folder_name_key(project_hash, parent_id.as_deref(), &folder_name);
+
+                match self.folder_names.get(&name_key) {
+                    Some(_) if parent_id.is_none() => ReturnMessage {
+                        code: ResultCode::DuplicateFolder,
+                        message: "Folder with the same name already exists".to_owned(),
+                        anchor: None,
+                    },
+                    Some(_) => ReturnMessage {
+                        code: ResultCode::DuplicateSubFolder,
+                        message: "Sub folder with the same name already exists in this folder"
+                            .to_owned(),
+                        anchor: None,
+                    },
                     None => {
-                        return ReturnMessage {
-                            result: 404,
-                            message: "File not found".to_owned(),
-                            transaction_hash: transaction_hash,
+                        let (transaction_type, message) = match parent_id {
+                            Some(_) => (
+                                TransactionType::AddSubFolder,
+                                "Sub folder added successfully",
+                            ),
+                            None => (TransactionType::AddFolder, "Folder added successfully"),
                         };
+
+                        let anchor = self.next_anchor();
+                        let _hash = folder_id(project_hash, anchor.sequence.0);
+                        let log_index =
+                            p.link(&_hash, UpdateLogs::new(anchor.clone(), transaction_type));
+                        let mut folder = Folder::create_folder(
+                            _hash.to_string(),
+                            project_id,
+                            parent_id,
+                            folder_name,
+                            log_index,
+                        );
+                        folder.paid_by = Some(caller.to_string());
+
+                        self.folders
+                            .insert(&entity_key(project_hash, &_hash), &folder.into());
+                        self.folder_names.insert(&name_key, &_hash);
+                        p.folder_ids.insert(&_hash);
+
+                        self.projects.insert(&project_hash.to_owned(), &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: message.to_owned(),
+                            anchor: Some(anchor),
+                        }
                     }
                 }
             }
-            None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
-            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
         }
     }
 
-    pub fn add_validator(
+    /// Renames a folder. Its hash doesn't change, so links to it keep
+    /// working, and its old name is free for another folder.
+    pub fn rename_folder(
         &mut self,
         project_hash: String,
-        file_hash: String,
-        validator_id: String,
-        validator_ip: String,
-        validator_email: String,
-        validator_organization: String,
-        can_sign: String,
+        folder_hash: String,
+        folder_name: String,
     ) -> ReturnMessage {
-        let transaction_hash = "Transaction Hash".to_owned();
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
 
-        match self.projects.get(&project_hash) {
+        match self.projects.get(&project_hash).map(Project::from) {
             Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
-
-                match p.files.iter().position(|r| r.file_hash == file_hash) {
-                    Some(_index) => {
-                        p.files[_index].validators.push(Validator::create_validator(
-                            validator_id,
-                            validator_ip,
-                            validator_email,
-                            validator_organization,
-                            can_sign,
-                            transaction_hash.to_string(),
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
+
+                if !self.can(&p, &caller, Permission::ManageFolders) {
+                    return ReturnMessage::unauthorized();
+                }
+
+                let key = entity_key(&project_hash, &folder_hash);
+
+                match self.folders.get(&key).map(Folder::from) {
+                    Some(mut folder) => {
+                        if folder.folder_name == folder_name {
+                            return ReturnMessage {
+                                code: ResultCode::Unchanged,
+                                message: "Folder already has this name".to_owned(),
+                                anchor: None,
+                            };
+                        }
+
+                        let parent_id = folder.parent_id.as_deref();
+                        let name_key = folder_name_key(&project_hash, parent_id, &folder_name);
+
+                        if self.folder_names.contains_key(&name_key) {
+                            return ReturnMessage::duplicate_folder_name();
+                        }
+
+                        let anchor = self.next_anchor();
+                        folder.update_logs.push(p.link(
+                            &folder_hash,
+                            UpdateLogs::change(
+                                anchor.clone(),
+                                TransactionType::RenameFolder,
+                                &folder.folder_name,
+                                &folder_name,
+                            ),
                         ));
+                        self.folder_names.remove(&folder_name_key(
+                            &project_hash,
+                            parent_id,
+                            &folder.folder_name,
+                        ));
+                        self.folder_names.insert(&name_key, &folder_hash);
+                        folder.folder_name = folder_name;
 
-                        self.projects.insert(&project_hash, &p);
+                        self.folders.insert(&key, &folder.into());
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
 
-                        return ReturnMessage {
-                            result: 200,
-                            message: "File validator added successfully".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                    None => {
-                        return ReturnMessage {
-                            result: 409,
-                            message: "File does not exist".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "Folder renamed successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
                     }
+                    None => ReturnMessage::folder_not_found(),
                 }
             }
-            None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
-            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
         }
     }
 
-    pub fn add_validator_access(
+    /// Moves a folder, with everything in it, under `parent_id`, or to the
+    /// top level when `parent_id` is `None`. A folder can't be moved into
+    /// itself or any of its sub folders. Like a rename, it keeps its hash.
+    pub fn move_folder(
         &mut self,
         project_hash: String,
-        file_hash: String,
-        validator_id: String,
-        validator_email: String,
+        folder_hash: String,
+        parent_id: Option<String>,
     ) -> ReturnMessage {
-        let transaction_hash = "Transaction Hash".to_owned();
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
 
-        match self.projects.get(&project_hash) {
+        match self.projects.get(&project_hash).map(Project::from) {
             Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
 
-                match p.files.iter().position(|r| r.file_hash == file_hash) {
-                    Some(f_index) => {
-                        match p.files[f_index]
-                            .validators
-                            .iter()
-                            .position(|r| r.validator_id == validator_id)
-                        {
-                            Some(_index) => {
-                                if p.files[f_index].validators[_index].is_revoked == true {
-                                    p.files[f_index].validators[_index].is_revoked = false;
-
-                                    self.projects.insert(&project_hash, &p);
-                                } else {
-                                    return ReturnMessage {
-                                        result: 200,
-                                        message: "File validator's access is already enabled"
-                                            .to_owned(),
-                                        transaction_hash: transaction_hash,
-                                    };
-                                }
+                if !self.can(&p, &caller, Permission::ManageFolders) {
+                    return ReturnMessage::unauthorized();
+                }
+
+                let key = entity_key(&project_hash, &folder_hash);
+
+                match self.folders.get(&key).map(Folder::from) {
+                    Some(mut folder) => {
+                        if folder.parent_id == parent_id {
+                            return ReturnMessage {
+                                code: ResultCode::Unchanged,
+                                message: "Folder is already in this folder".to_owned(),
+                                anchor: None,
+                            };
+                        }
 
+                        if let Some(parent_id) = &parent_id {
+                            if !self
+                                .folders
+                                .contains_key(&entity_key(&project_hash, parent_id))
+                            {
                                 return ReturnMessage {
-                                    result: 200,
-                                    message: "File added successfully".to_owned(),
-                                    transaction_hash: transaction_hash,
+                                    code: ResultCode::FolderNotFound,
+                                    message: "Parent folder not found".to_owned(),
+                                    anchor: None,
                                 };
                             }
-                            None => {
+
+                            if self.is_within(&project_hash, parent_id, &folder_hash) {
                                 return ReturnMessage {
-                                    result: 409,
-                                    message: "File validator does not exist".to_owned(),
-                                    transaction_hash: transaction_hash,
+                                    code: ResultCode::InvalidFolderMove,
+                                    message: "Folder can't be moved into itself or its sub folders"
+                                        .to_owned(),
+                                    anchor: None,
                                 };
                             }
                         }
+
+                        let name_key = folder_name_key(
+                            &project_hash,
+                            parent_id.as_deref(),
+                            &folder.folder_name,
+                        );
+
+                        if self.folder_names.contains_key(&name_key) {
+                            return ReturnMessage::duplicate_folder_name();
+                        }
+
+                        let anchor = self.next_anchor();
+                        folder.update_logs.push(p.link(
+                            &folder_hash,
+                            UpdateLogs::change(
+                                anchor.clone(),
+                                TransactionType::MoveFolder,
+                                folder.parent_id.as_deref().unwrap_or(""),
+                                parent_id.as_deref().unwrap_or(""),
+                            ),
+                        ));
+                        self.folder_names.remove(&folder_name_key(
+                            &project_hash,
+                            folder.parent_id.as_deref(),
+                            &folder.folder_name,
+                        ));
+                        self.folder_names.insert(&name_key, &folder_hash);
+                        folder.parent_id = parent_id;
+
+                        self.folders.insert(&key, &folder.into());
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "Folder moved successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
                     }
-                    None => {
-                        return ReturnMessage {
-                            result: 409,
-                            message: "File does not exist".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
+                    None => ReturnMessage::folder_not_found(),
                 }
             }
-            None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
-            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
         }
     }
 
-    pub fn remove_validator_access(
+    /// Deletes a folder and its sub folders. With
+    /// `FolderDeletePolicy::RefuseIfNotEmpty` only an empty folder can be
+    /// deleted; with `FolderDeletePolicy::ArchiveFiles` the files in it are
+    /// archived, which also takes `Permission::ArchiveFiles`. Archived files
+    /// keep their `folder_id`. Anchored batches are permanent, so a folder
+    /// holding any, directly or below, can't be deleted under either policy.
+    /// Freed storage is credited to the accounts that paid for it.
+    pub fn delete_folder(
         &mut self,
         project_hash: String,
-        file_hash: String,
-        validator_id: String,
-        validator_email: String,
+        folder_hash: String,
+        policy: FolderDeletePolicy,
     ) -> ReturnMessage {
-        let transaction_hash = "Transaction Hash".to_owned();
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
 
-        match self.projects.get(&project_hash) {
+        match self.projects.get(&project_hash).map(Project::from) {
             Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
 
-                match p.files.iter().position(|r| r.file_hash == file_hash) {
-                    Some(f_index) => {
-                        match p.files[f_index]
-                            .validators
+                if !self.can(&p, &caller, Permission::ManageFolders) {
+                    return ReturnMessage::unauthorized();
+                }
+
+                match self
+                    .folders
+                    .get(&entity_key(&project_hash, &folder_hash))
+                    .map(Folder::from)
+                {
+                    Some(folder) => {
+                        let mut folders = vec![folder];
+                        folders.extend(self.descendants(&p, &folder_hash));
+
+                        if folders.iter().any(|r| {
+                            self.folder_batches
+                                .contains_key(&entity_key(&project_hash, &r.folder_hash))
+                        }) {
+                            return ReturnMessage {
+                                code: ResultCode::FolderNotEmpty,
+                                message: "Folder holds anchored batches".to_owned(),
+                                anchor: None,
+                            };
+                        }
+
+                        let file_ids: Vec<String> = folders
                             .iter()
-                            .position(|r| r.validator_id == validator_id)
+                            .flat_map(|r| self.folder_file_ids(&project_hash, &r.folder_hash))
+                            .collect();
+
+                        if policy == FolderDeletePolicy::RefuseIfNotEmpty
+                            && (folders.len() > 1 || !file_ids.is_empty())
                         {
-                            Some(_index) => {
-                                if p.files[f_index].validators[_index].is_revoked == false {
-                                    p.files[f_index].validators[_index].is_revoked = true;
-
-                                    self.projects.insert(&project_hash, &p);
-                                } else {
-                                    return ReturnMessage {
-                                        result: 200,
-                                        message: "File validator's access is already disabled"
-                                            .to_owned(),
-                                        transaction_hash: transaction_hash,
-                                    };
-                                }
+                            return ReturnMessage {
+                                code: ResultCode::FolderNotEmpty,
+                                message: "Folder is not empty".to_owned(),
+                                anchor: None,
+                            };
+                        }
 
-                                return ReturnMessage {
-                                    result: 200,
-                                    message: "File added successfully".to_owned(),
-                                    transaction_hash: transaction_hash,
-                                };
-                            }
-                            None => {
-                                return ReturnMessage {
-                                    result: 409,
-                                    message: "File validator does not exist".to_owned(),
-                                    transaction_hash: transaction_hash,
-                                };
+                        if !file_ids.is_empty() && !self.can(&p, &caller, Permission::ArchiveFiles)
+                        {
+                            return ReturnMessage::unauthorized();
+                        }
+
+                        let files: Vec<File> = file_ids
+                            .iter()
+                            .filter_map(|id| {
+                                self.files
+                                    .get(&entity_key(&project_hash, id))
+                                    .map(File::from)
+                            })
+                            .filter(|r| !r.file_status.is_terminal())
+                            .collect();
+
+                        if folders.len() + files.len() > MAX_EVENTS_PER_CALL {
+                            return ReturnMessage {
+                                code: ResultCode::TooManyItems,
+                                message: format!(
+                                    "Deleting this folder touches more than {} folders and files, delete its sub folders first",
+                                    MAX_EVENTS_PER_CALL
+                                ),
+                                anchor: None,
+                            };
+                        }
+
+                        let anchor = self.next_anchor();
+                        collect_events();
+
+                        for mut file in files {
+                            file.update_logs.push(p.link(
+                                &file.file_hash,
+                                UpdateLogs::change(
+                                    anchor.clone(),
+                                    TransactionType::UpdateFile,
+                                    file.file_status,
+                                    FileStatus::Grey,
+                                ),
+                            ));
+                            file.file_status = FileStatus::Grey;
+                            p.index_expiry(&file);
+
+                            self.files
+                                .insert(&entity_key(&project_hash, &file.file_hash), &file.into());
+                        }
+
+                        // Freed bytes go back to whoever paid for them, not to
+                        // the caller, who only pays for the log entries.
+                        let mut freed = 0;
+
+                        // Children go before their parents.
+                        for folder in folders.into_iter().rev() {
+                            p.link(
+                                &folder.folder_hash,
+                                UpdateLogs::change(
+                                    anchor.clone(),
+                                    TransactionType::DeleteFolder,
+                                    &folder.folder_name,
+                                    "",
+                                ),
+                            );
+
+                            let key = entity_key(&project_hash, &folder.folder_hash);
+                            if let Some(mut file_ids) = self.folder_files.remove(&key) {
+                                for file_hash in file_ids.to_vec() {
+                                    let before = env::storage_usage();
+                                    file_ids.remove(&file_hash);
+
+                                    let paid_by = self
+                                        .files
+                                        .get(&entity_key(&project_hash, &file_hash))
+                                        .map(File::from)
+                                        .and_then(|r| r.paid_by);
+                                    freed += self.refund_storage(paid_by.as_deref(), before);
+                                }
                             }
+
+                            let before = env::storage_usage();
+                            self.folder_names.remove(&folder_name_key(
+                                &project_hash,
+                                folder.parent_id.as_deref(),
+                                &folder.folder_name,
+                            ));
+                            self.folders.remove(&key);
+                            p.folder_ids.remove(&folder.folder_hash);
+                            freed += self.refund_storage(folder.paid_by.as_deref(), before);
+                        }
+
+                        flush_events();
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage - freed);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "Folder deleted successfully".to_owned(),
+                            anchor: Some(anchor),
                         }
                     }
-                    None => {
-                        return ReturnMessage {
-                            result: 409,
-                            message: "File does not exist".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
+                    None => ReturnMessage::folder_not_found(),
                 }
             }
-            None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
-            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
         }
     }
 
-    pub fn update_validator_after_file_validation(
+    /// Grants `role` on the project to `user_id`. Defaults to `Role::Editor`
+    /// when no role is given, matching what users could do before roles existed.
+    pub fn add_user(
         &mut self,
         project_hash: String,
-        file_hash: String,
-        validator_email: String,
-        file_status: String,
+        user_name: String,
+        user_id: String,
+        role: Option<Role>,
     ) -> ReturnMessage {
-        let transaction_hash = "Transaction Hash".to_owned();
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+        let role = role.unwrap_or(Role::Editor);
 
-        match self.projects.get(&project_hash) {
+        match self.projects.get(&project_hash).map(Project::from) {
             Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
 
-                match p.files.iter().position(|r| r.file_hash == file_hash) {
-                    Some(f_index) => {
-                        match p.files[f_index]
-                            .validators
-                            .iter()
-                            .position(|r| r.validator_email == validator_email)
-                        {
-                            Some(_index) => {
-                                p.files[f_index].validators[_index].file_status = file_status;
-                                //p.files[f_index].validators[_index].file_validation_hash = transaction_hash;
+                if !self.can_assign_role(&p, &caller, role) {
+                    return ReturnMessage::unauthorized();
+                }
 
-                                self.projects.insert(&project_hash, &p);
+                let key = entity_key(&project_hash, &user_id);
 
-                                return ReturnMessage {
-                                    result: 200,
-                                    message: "File added successfully".to_owned(),
-                                    transaction_hash: transaction_hash,
-                                };
-                            }
-                            None => {
-                                return ReturnMessage {
-                                    result: 409,
-                                    message: "File validator does not exist".to_owned(),
-                                    transaction_hash: transaction_hash,
-                                };
-                            }
-                        }
-                    }
+                match self.users.get(&key).map(User::from) {
+                    Some(_) => ReturnMessage {
+                        code: ResultCode::DuplicateUser,
+                        message: "User already exists".to_owned(),
+                        anchor: None,
+                    },
                     None => {
-                        return ReturnMessage {
-                            result: 409,
-                            message: "File does not exist".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                }
-            }
-            None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
-            }
-        }
-    }
+                        let anchor = self.next_anchor();
+                        let log_index = p.link(
+                            &user_id,
+                            UpdateLogs::new(anchor.clone(), TransactionType::AddUser),
+                        );
+                        self.users.insert(
+                            &key,
+                            &User::create_user(user_name, user_id.to_string(), role, log_index)
+                                .into(),
+                        );
+                        p.user_ids.insert(&user_id);
 
-    pub fn query_project(&mut self, project_hash: String) -> ReturnMessage {
-        let transaction_hash = "Transaction Hash".to_owned();
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
 
-        match self.projects.get(&project_hash) {
-            Some(p) => {
-                return ReturnMessage {
-                    result: 200,
-                    message: "Project found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
-            }
-            None => {
-                return ReturnMessage {
-                    result: 200,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "User added successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
+                    }
+                }
             }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
         }
     }
 
-    pub fn add_supplier(
+    /// Moves an existing user to a different role. The project owner's role
+    /// can't be changed, and only the owner may promote to or demote from admin.
+    pub fn change_user_role(
         &mut self,
         project_hash: String,
-        category: String,
-        contact_name: String,
-        supplier_id: String,
-        supplier_email: String,
-        company_name: String,
-        company_website: String,
-        requested_documents: String,
+        user_id: String,
+        role: Role,
     ) -> ReturnMessage {
-        let transaction_hash = "Transaction Hash".to_owned();
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
 
-        match self.projects.get(&project_hash) {
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => {
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
+
+                let key = entity_key(&project_hash, &user_id);
+
+                match self.users.get(&key).map(User::from) {
+                    Some(mut user) => {
+                        if !self.can_assign_role(&p, &caller, user.role)
+                            || !self.can_assign_role(&p, &caller, role)
+                        {
+                            return ReturnMessage::unauthorized();
+                        }
+
+                        if user.role == role {
+                            return ReturnMessage {
+                                code: ResultCode::Unchanged,
+                                message: "User already has this role".to_owned(),
+                                anchor: None,
+                            };
+                        }
+
+                        let anchor = self.next_anchor();
+                        user.update_logs.push(p.link(
+                            &user_id,
+                            UpdateLogs::change(
+                                anchor.clone(),
+                                TransactionType::ChangeUserRole,
+                                format!("{:?}", user.role),
+                                format!("{:?}", role),
+                            ),
+                        ));
+                        user.role = role;
+
+                        self.users.insert(&key, &user.into());
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "User's role changed successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
+                    }
+                    None => ReturnMessage {
+                        code: ResultCode::UserNotFound,
+                        message: "User not found".to_owned(),
+                        anchor: None,
+                    },
+                }
+            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    pub fn add_user_access(&mut self, project_hash: String, user_id: String) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => {
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
+
+                let key = entity_key(&project_hash, &user_id);
+
+                match self.users.get(&key).map(User::from) {
+                    Some(mut user) => {
+                        if !self.can_assign_role(&p, &caller, user.role) {
+                            return ReturnMessage::unauthorized();
+                        }
+
+                        if !user.is_revoked {
+                            return ReturnMessage {
+                                code: ResultCode::Unchanged,
+                                message: "User's access is already enabled".to_owned(),
+                                anchor: None,
+                            };
+                        }
+
+                        let anchor = self.next_anchor();
+                        user.update_logs.push(p.link(
+                            &user_id,
+                            UpdateLogs::change(
+                                anchor.clone(),
+                                TransactionType::AddUserAccess,
+                                true,
+                                false,
+                            ),
+                        ));
+                        user.is_revoked = false;
+
+                        self.users.insert(&key, &user.into());
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "User's access added successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
+                    }
+                    None => ReturnMessage {
+                        code: ResultCode::UserNotFound,
+                        message: "User not found".to_owned(),
+                        anchor: None,
+                    },
+                }
+            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    pub fn remove_user_access(&mut self, project_hash: String, user_id: String) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => {
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
+
+                let key = entity_key(&project_hash, &user_id);
+
+                match self.users.get(&key).map(User::from) {
+                    Some(mut user) => {
+                        if !self.can_assign_role(&p, &caller, user.role) {
+                            return ReturnMessage::unauthorized();
+                        }
+
+                        if user.is_revoked {
+                            return ReturnMessage {
+                                code: ResultCode::Unchanged,
+                                message: "User's access is already disabled".to_owned(),
+                                anchor: None,
+                            };
+                        }
+
+                        let anchor = self.next_anchor();
+                        user.update_logs.push(p.link(
+                            &user_id,
+                            UpdateLogs::change(
+                                anchor.clone(),
+                                TransactionType::RemoveUserAccess,
+                                false,
+                                true,
+                            ),
+                        ));
+                        user.is_revoked = true;
+
+                        self.users.insert(&key, &user.into());
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "User's access removed successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
+                    }
+                    None => ReturnMessage {
+                        code: ResultCode::UserNotFound,
+                        message: "User not found".to_owned(),
+                        anchor: None,
+                    },
+                }
+            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    /// Adds a file to `folder_id`, uploaded by `user_id`. The folder must be
+    /// in the project and the uploader a user whose access hasn't been
+    /// revoked and whose role may add files. `expires_at` is a block
+    /// timestamp in nanoseconds, which must still be in the future; files
+    /// without one never expire.
+    pub fn add_file(
+        &mut self,
+        project_hash: String,
+        file_hash: String,
+        title: String,
+        user_id: String,
+        folder_id: String,
+        expires_at: Option<U64>,
+    ) -> ReturnMessage {
+        self.insert_file(
+            project_hash,
+            file_hash,
+            title,
+            user_id,
+            folder_id,
+            expires_at,
+            false,
+        )
+    }
+
+    /// Like `add_file`, for documents whose hash shouldn't be public.
+    /// `commitment` is `file_commitment(salt, file_hash)`, the `derive_id` of
+    /// the salt and the document hash, computed off chain so neither
+    /// ever appears in a transaction. Anyone holding both can check it with
+    /// `verify_commitment`.
+    pub fn add_file_commitment(
+        &mut self,
+        project_hash: String,
+        commitment: String,
+        title: String,
+        user_id: String,
+        folder_id: String,
+        expires_at: Option<U64>,
+    ) -> ReturnMessage {
+        if !is_sha256_hex(&commitment) {
+            return ReturnMessage {
+                code: ResultCode::InvalidCommitment,
+                message: "Commitment must be a lowercase hex sha256 digest".to_owned(),
+                anchor: None,
+            };
+        }
+
+        self.insert_file(
+            project_hash,
+            commitment,
+            title,
+            user_id,
+            folder_id,
+            expires_at,
+            true,
+        )
+    }
+
+    /// Adds `file_hash` as the next version of `previous_file_hash`, which
+    /// is marked superseded. The new version goes in `folder_id`, or the
+    /// previous version's folder when that's `None`. With `carry_validators`
+    /// the previous version's validators whose access hasn't been revoked
+    /// are added to the new version too, without their results: they
+    /// validated the old document, not this one. Only the latest version of
+    /// a file can be replaced. Files added with `add_file_commitment` can't
+    /// be versioned, as the new version's hash would link the committed
+    /// document to a hash in the clear.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_file_version(
+        &mut self,
+        project_hash: String,
+        previous_file_hash: String,
+        file_hash: String,
+        title: String,
+        user_id: String,
+        folder_id: Option<String>,
+        expires_at: Option<U64>,
+        carry_validators: bool,
+    ) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => {
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
+
+                if !self.can(&p, &caller, Permission::AddFiles) {
+                    return ReturnMessage::unauthorized();
+                }
+
+                let previous_key = entity_key(&project_hash, &previous_file_hash);
+
+                match self.files.get(&previous_key).map(File::from) {
+                    Some(mut previous) => {
+                        if let Some(superseded_by) = &previous.superseded_by {
+                            return ReturnMessage {
+                                code: ResultCode::FileSuperseded,
+                                message: format!(
+                                    "File has already been superseded by {}",
+                                    superseded_by
+                                ),
+                                anchor: None,
+                            };
+                        }
+
+                        if previous.committed {
+                            return ReturnMessage {
+                                code: ResultCode::InvalidCommitment,
+                                message: "A committed file can't be versioned in the clear"
+                                    .to_owned(),
+                                anchor: None,
+                            };
+                        }
+
+                        let key = entity_key(&project_hash, &file_hash);
+
+                        if self.files.contains_key(&key) {
+                            return ReturnMessage {
+                                code: ResultCode::DuplicateFile,
+                                message: "File already exists".to_owned(),
+                                anchor: None,
+                            };
+                        }
+
+                        let folder_id = folder_id.unwrap_or_else(|| previous.folder_id.to_string());
+
+                        if let Some(error) =
+                            self.check_file_placement(&project_hash, &folder_id, &user_id)
+                        {
+                            return error;
+                        }
+
+                        if let Some(error) = check_expiry(expires_at) {
+                            return error;
+                        }
+
+                        let validators: Vec<Validator> = if carry_validators {
+                            previous
+                                .validators
+                                .iter()
+                                .filter_map(|id| {
+                                    self.validators
+                                        .get(&entity_key(
+                                            &project_hash,
+                                            &validator_entity_id(&previous_file_hash, id),
+                                        ))
+                                        .map(Validator::from)
+                                })
+                                .filter(|r| !r.is_revoked)
+                                .collect()
+                        } else {
+                            Vec::new()
+                        };
+
+                        if validators.len() + 2 > MAX_EVENTS_PER_CALL {
+                            return ReturnMessage {
+                                code: ResultCode::TooManyItems,
+                                message: format!(
+                                    "A file version can carry at most {} validators",
+                                    MAX_EVENTS_PER_CALL - 2
+                                ),
+                                anchor: None,
+                            };
+                        }
+
+                        let anchor = self.next_anchor();
+                        collect_events();
+                        previous.update_logs.push(p.link(
+                            &previous_file_hash,
+                            UpdateLogs::change(
+                                anchor.clone(),
+                                TransactionType::SupersedeFile,
+                                "",
+                                &file_hash,
+                            ),
+                        ));
+                        previous.superseded_by = Some(file_hash.to_string());
+
+                        let log_index = p.link(
+                            &file_hash,
+                            UpdateLogs::change(
+                                anchor.clone(),
+                                TransactionType::AddFileVersion,
+                                &previous_file_hash,
+                                &file_hash,
+                            ),
+                        );
+                        let mut file = File::create_file(
+                            file_hash.to_string(),
+                            title,
+                            user_id,
+                            folder_id,
+                            expires_at,
+                            log_index,
+                        );
+                        file.previous_version = Some(previous_file_hash);
+                        file.paid_by = Some(caller.to_string());
+
+                        for validator in validators {
+                            let entity_id =
+                                validator_entity_id(&file_hash, &validator.validator_id);
+                            let log_index = p.link(
+                                &entity_id,
+                                UpdateLogs::new(anchor.clone(), TransactionType::AddValidator),
+                            );
+                            self.validators.insert(
+                                &entity_key(&project_hash, &entity_id),
+                                &Validator::create_validator(
+                                    validator.validator_id.to_string(),
+                                    validator.validator_ip,
+                                    validator.validator_email,
+                                    validator.validator_organization,
+                                    validator.can_sign,
+                                    log_index,
+                                )
+                                .into(),
+                            );
+                            file.validators.push(validator.validator_id);
+                        }
+
+                        self.index_file(&project_hash, &file.folder_id, &file_hash);
+                        p.index_expiry(&file);
+                        p.index_expiry(&previous);
+                        self.files.insert(&key, &file.into());
+                        self.files.insert(&previous_key, &previous.into());
+                        p.file_ids.insert(&file_hash);
+
+                        flush_events();
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "File version added successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
+                    }
+                    None => ReturnMessage {
+                        code: ResultCode::FileNotFound,
+                        message: "File not found".to_owned(),
+                        anchor: None,
+                    },
+                }
+            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    /// Anchors `merkle_root`, the root of a Merkle tree over `leaf_count`
+    /// document hashes, in `folder_id`. The documents themselves never go on
+    /// chain; each is proven later with `verify_inclusion`. Leaves and inner
+    /// nodes are hashed with `merkle_leaf` and `merkle_node`.
+    pub fn anchor_batch(
+        &mut self,
+        project_hash: String,
+        merkle_root: String,
+        title: String,
+        folder_id: String,
+        leaf_count: u64,
+    ) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => {
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
+
+                if !self.can(&p, &caller, Permission::AddFiles) {
+                    return ReturnMessage::unauthorized();
+                }
+
+                if !self
+                    .folders
+                    .contains_key(&entity_key(&project_hash, &folder_id))
+                {
+                    return ReturnMessage::folder_not_found();
+                }
+
+                if !is_sha256_hex(&merkle_root) || leaf_count == 0 {
+                    return ReturnMessage {
+                        code: ResultCode::InvalidMerkleRoot,
+                        message:
+                            "Merkle root must be a lowercase hex sha256 digest over at least one leaf"
+                                .to_owned(),
+                        anchor: None,
+                    };
+                }
+
+                let key = entity_key(&project_hash, &merkle_root);
+
+                if self.batches.contains_key(&key) {
+                    return ReturnMessage {
+                        code: ResultCode::DuplicateBatch,
+                        message: "Batch already exists".to_owned(),
+                        anchor: None,
+                    };
+                }
+
+                let anchor = self.next_anchor();
+                let log_index = p.link(
+                    &merkle_root,
+                    UpdateLogs::new(anchor.clone(), TransactionType::AnchorBatch),
+                );
+                self.batches.insert(
+                    &key,
+                    &Batch::create_batch(
+                        merkle_root.to_string(),
+                        title,
+                        folder_id.to_string(),
+                        leaf_count,
+                        log_index,
+                    )
+                    .into(),
+                );
+                p.batch_ids.insert(&merkle_root);
+
+                let mut project_ids = self.batch_projects.get(&merkle_root).unwrap_or_else(|| {
+                    UnorderedSet::new(StorageKey::BatchProjectIds {
+                        merkle_root: merkle_root.to_string(),
+                    })
+                });
+                project_ids.insert(&project_hash);
+                self.batch_projects.insert(&merkle_root, &project_ids);
+
+                let folder_key = entity_key(&project_hash, &folder_id);
+                let mut batch_ids = self.folder_batches.get(&folder_key).unwrap_or_else(|| {
+                    UnorderedSet::new(StorageKey::FolderBatchIds {
+                        folder_key: folder_key.to_string(),
+                    })
+                });
+                batch_ids.insert(&merkle_root);
+                self.folder_batches.insert(&folder_key, &batch_ids);
+
+                self.projects.insert(&project_hash, &p.into());
+                self.settle_storage(&caller, initial_storage);
+
+                ReturnMessage {
+                    code: ResultCode::Ok,
+                    message: "Batch anchored successfully".to_owned(),
+                    anchor: Some(anchor),
+                }
+            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    pub fn accept_file(&mut self, project_hash: String, file_hash: String) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => {
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
+
+                if !self.can(&p, &caller, Permission::ReviewFiles) {
+                    return ReturnMessage::unauthorized();
+                }
+
+                let key = entity_key(&project_hash, &file_hash);
+
+                match self.files.get(&key).map(File::from) {
+                    Some(mut file) => {
+                        if file.file_status == FileStatus::Green {
+                            return ReturnMessage {
+                                code: ResultCode::Unchanged,
+                                message: "File status already accepted".to_owned(),
+                                anchor: None,
+                            };
+                        }
+
+                        if let Some(error) =
+                            self.check_transition(&p, &caller, file.file_status, FileStatus::Green)
+                        {
+                            return error;
+                        }
+
+                        let anchor = self.next_anchor();
+                        file.update_logs.push(p.link(
+                            &file_hash,
+                            UpdateLogs::change(
+                                anchor.clone(),
+                                TransactionType::AcceptFile,
+                                file.file_status,
+                                FileStatus::Green,
+                            ),
+                        ));
+                        file.file_status = FileStatus::Green;
+
+                        self.files.insert(&key, &file.into());
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "File status accepted successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
+                    }
+                    None => ReturnMessage {
+                        code: ResultCode::FileNotFound,
+                        message: "File not found".to_owned(),
+                        anchor: None,
+                    },
+                }
+            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    pub fn reject_file(&mut self, project_hash: String, file_hash: String) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => {
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
+
+                if !self.can(&p, &caller, Permission::ReviewFiles) {
+                    return ReturnMessage::unauthorized();
+                }
+
+                let key = entity_key(&project_hash, &file_hash);
+
+                match self.files.get(&key).map(File::from) {
+                    Some(mut file) => {
+                        if file.file_status == FileStatus::Red {
+                            return ReturnMessage {
+                                code: ResultCode::Unchanged,
+                                message: "File status already rejected".to_owned(),
+                                anchor: None,
+                            };
+                        }
+
+                        if let Some(error) =
+                            self.check_transition(&p, &caller, file.file_status, FileStatus::Red)
+                        {
+                            return error;
+                        }
+
+                        let anchor = self.next_anchor();
+                        file.update_logs.push(p.link(
+                            &file_hash,
+                            UpdateLogs::change(
+                                anchor.clone(),
+                                TransactionType::RejectFile,
+                                file.file_status,
+                                FileStatus::Red,
+                            ),
+                        ));
+                        file.file_status = FileStatus::Red;
+
+                        self.files.insert(&key, &file.into());
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "File status rejected successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
+                    }
+                    None => ReturnMessage {
+                        code: ResultCode::FileNotFound,
+                        message: "File not found".to_owned(),
+                        anchor: None,
+                    },
+                }
+            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    pub fn update_file(
+        &mut self,
+        project_hash: String,
+        file_hash: String,
+        update_status: FileStatus,
+    ) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => {
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
+
+                if !self.can(&p, &caller, Permission::ReviewFiles) {
+                    return ReturnMessage::unauthorized();
+                }
+
+                let key = entity_key(&project_hash, &file_hash);
+
+                match self.files.get(&key).map(File::from) {
+                    Some(mut file) => {
+                        if file.file_status == update_status {
+                            return ReturnMessage {
+                                code: ResultCode::Unchanged,
+                                message: "File status is already set".to_owned(),
+                                anchor: None,
+                            };
+                        }
+
+                        if let Some(error) =
+                            self.check_transition(&p, &caller, file.file_status, update_status)
+                        {
+                            return error;
+                        }
+
+                        let anchor = self.next_anchor();
+                        file.update_logs.push(p.link(
+                            &file_hash,
+                            UpdateLogs::change(
+                                anchor.clone(),
+                                TransactionType::UpdateFile,
+                                file.file_status,
+                                update_status,
+                            ),
+                        ));
+                        file.file_status = update_status;
+                        p.index_expiry(&file);
+
+                        self.files.insert(&key, &file.into());
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "File status updated successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
+                    }
+                    None => ReturnMessage {
+                        code: ResultCode::FileNotFound,
+                        message: "File not found".to_owned(),
+                        anchor: None,
+                    },
+                }
+            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_validator(
+        &mut self,
+        project_hash: String,
+        file_hash: String,
+        validator_id: String,
+        validator_ip: String,
+        validator_email: String,
+        validator_organization: String,
+        can_sign: String,
+    ) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => {
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
+
+                if !self.can(&p, &caller, Permission::ManageValidators) {
+                    return ReturnMessage::unauthorized();
+                }
+
+                let file_key = entity_key(&project_hash, &file_hash);
+
+                match self.files.get(&file_key).map(File::from) {
+                    Some(mut file) => {
+                        let entity_id = validator_entity_id(&file_hash, &validator_id);
+                        let key = entity_key(&project_hash, &entity_id);
+
+                        if self.validators.contains_key(&key) {
+                            return ReturnMessage {
+                                code: ResultCode::DuplicateValidator,
+                                message: "File validator already exists".to_owned(),
+                                anchor: None,
+                            };
+                        }
+
+                        let anchor = self.next_anchor();
+                        let log_index = p.link(
+                            &entity_id,
+                            UpdateLogs::new(anchor.clone(), TransactionType::AddValidator),
+                        );
+                        self.validators.insert(
+                            &key,
+                            &Validator::create_validator(
+                                validator_id.to_string(),
+                                validator_ip,
+                                validator_email,
+                                validator_organization,
+                                can_sign,
+                                log_index,
+                            )
+                            .into(),
+                        );
+                        file.validators.push(validator_id);
+
+                        self.files.insert(&file_key, &file.into());
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "File validator added successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
+                    }
+                    None => ReturnMessage {
+                        code: ResultCode::FileNotFound,
+                        message: "File not found".to_owned(),
+                        anchor: None,
+                    },
+                }
+            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    pub fn add_validator_access(
+        &mut self,
+        project_hash: String,
+        file_hash: String,
+        validator_id: String,
+    ) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => {
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
+
+                if !self.can(&p, &caller, Permission::ManageValidators) {
+                    return ReturnMessage::unauthorized();
+                }
+
+                if !self
+                    .files
+                    .contains_key(&entity_key(&project_hash, &file_hash))
+                {
+                    return ReturnMessage {
+                        code: ResultCode::FileNotFound,
+                        message: "File not found".to_owned(),
+                        anchor: None,
+                    };
+                }
+
+                let entity_id = validator_entity_id(&file_hash, &validator_id);
+                let key = entity_key(&project_hash, &entity_id);
+
+                match self.validators.get(&key).map(Validator::from) {
+                    Some(mut validator) => {
+                        if !validator.is_revoked {
+                            return ReturnMessage {
+                                code: ResultCode::Unchanged,
+                                message: "File validator's access is already enabled".to_owned(),
+                                anchor: None,
+                            };
+                        }
+
+                        let anchor = self.next_anchor();
+                        validator.update_logs.push(p.link(
+                            &entity_id,
+                            UpdateLogs::change(
+                                anchor.clone(),
+                                TransactionType::AddValidatorAccess,
+                                true,
+                                false,
+                            ),
+                        ));
+                        validator.is_revoked = false;
+
+                        self.validators.insert(&key, &validator.into());
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "File validator's access added successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
+                    }
+                    None => ReturnMessage {
+                        code: ResultCode::ValidatorNotFound,
+                        message: "File validator not found".to_owned(),
+                        anchor: None,
+                    },
+                }
+            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    pub fn remove_validator_access(
+        &mut self,
+        project_hash: String,
+        file_hash: String,
+        validator_id: String,
+    ) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => {
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
+
+                if !self.can(&p, &caller, Permission::ManageValidators) {
+                    return ReturnMessage::unauthorized();
+                }
+
+                if !self
+                    .files
+                    .contains_key(&entity_key(&project_hash, &file_hash))
+                {
+                    return ReturnMessage {
+                        code: ResultCode::FileNotFound,
+                        message: "File not found".to_owned(),
+                        anchor: None,
+                    };
+                }
+
+                let entity_id = validator_entity_id(&file_hash, &validator_id);
+                let key = entity_key(&project_hash, &entity_id);
+
+                match self.validators.get(&key).map(Validator::from) {
+                    Some(mut validator) => {
+                        if validator.is_revoked {
+                            return ReturnMessage {
+                                code: ResultCode::Unchanged,
+                                message: "File validator's access is already disabled".to_owned(),
+                                anchor: None,
+                            };
+                        }
+
+                        let anchor = self.next_anchor();
+                        validator.update_logs.push(p.link(
+                            &entity_id,
+                            UpdateLogs::change(
+                                anchor.clone(),
+                                TransactionType::RemoveValidatorAccess,
+                                false,
+                                true,
+                            ),
+                        ));
+                        validator.is_revoked = true;
+
+                        self.validators.insert(&key, &validator.into());
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "File validator's access removed successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
+                    }
+                    None => ReturnMessage {
+                        code: ResultCode::ValidatorNotFound,
+                        message: "File validator not found".to_owned(),
+                        anchor: None,
+                    },
+                }
+            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    /// Records a validator's verdict on a file. Callable by accounts with
+    /// `Permission::ReviewFiles` and by the validator account itself while its
+    /// access is not revoked. The verdict's anchor keeps who recorded it, and
+    /// only verdicts the validator recorded itself count as its attestation
+    /// in `verify_document`.
+    pub fn update_validator_after_file_validation(
+        &mut self,
+        project_hash: String,
+        file_hash: String,
+        validator_email: String,
+        file_status: FileStatus,
+    ) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+
+        if !file_status.is_verdict() {
+            return ReturnMessage {
+                code: ResultCode::InvalidValidationResult,
+                message: format!("{} is not a valid validation result", file_status),
+                anchor: None,
+            };
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => match self
+                .files
+                .get(&entity_key(&project_hash, &file_hash))
+                .map(File::from)
+            {
+                Some(file) => {
+                    if p.legal_hold.is_some() {
+                        return ReturnMessage::legal_hold();
+                    }
+
+                    if file.file_status.is_terminal() {
+                        return ReturnMessage {
+                            code: ResultCode::IllegalStatusTransition,
+                            message: format!(
+                                "File status {} is final and can't be validated",
+                                file.file_status
+                            ),
+                            anchor: None,
+                        };
+                    }
+
+                    let found = file.validators.iter().find_map(|validator_id| {
+                        let key = entity_key(
+                            &project_hash,
+                            &validator_entity_id(&file_hash, validator_id),
+                        );
+                        self.validators
+                            .get(&key)
+                            .map(Validator::from)
+                            .filter(|r| r.validator_email == validator_email)
+                            .map(|r| (key, r))
+                    });
+
+                    match found {
+                        Some((key, mut validator)) => {
+                            let is_self = validator.validator_id == caller && !validator.is_revoked;
+
+                            if !is_self && !self.can(&p, &caller, Permission::ReviewFiles) {
+                                return ReturnMessage::unauthorized();
+                            }
+
+                            let anchor = self.next_anchor();
+                            validator.update_logs.push(
+                                p.link(
+                                    &validator_entity_id(&file_hash, &validator.validator_id),
+                                    UpdateLogs::change(
+                                        anchor.clone(),
+                                        TransactionType::ValidateFile,
+                                        validator
+                                            .file_status
+                                            .map_or("".to_owned(), |r| r.to_string()),
+                                        file_status,
+                                    ),
+                                ),
+                            );
+                            validator.file_status = Some(file_status);
+                            validator.file_validation_anchor = Some(anchor.clone());
+
+                            self.validators.insert(&key, &validator.into());
+                            self.projects.insert(&project_hash, &p.into());
+                            self.settle_storage(&caller, initial_storage);
+
+                            ReturnMessage {
+                                code: ResultCode::Ok,
+                                message: "File validation recorded successfully".to_owned(),
+                                anchor: Some(anchor),
+                            }
+                        }
+                        None => ReturnMessage {
+                            code: ResultCode::ValidatorNotFound,
+                            message: "File validator not found".to_owned(),
+                            anchor: None,
+                        },
+                    }
+                }
+                None => ReturnMessage {
+                    code: ResultCode::FileNotFound,
+                    message: "File not found".to_owned(),
+                    anchor: None,
+                },
+            },
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    pub fn get_project_history(
+        &self,
+        project_hash: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<UpdateLogsView> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .map(|p| history_page(&p, &p.update_logs, from_index, limit))
+            .unwrap_or_default()
+    }
+
+    pub fn get_folder_history(
+        &self,
+        project_hash: String,
+        folder_hash: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<UpdateLogsView> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .and_then(|p| {
+                self.folders
+                    .get(&entity_key(&project_hash, &folder_hash))
+                    .map(Folder::from)
+                    .map(|r| history_page(&p, &r.update_logs, from_index, limit))
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get_user_history(
+        &self,
+        project_hash: String,
+        user_id: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<UpdateLogsView> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .and_then(|p| {
+                self.users
+                    .get(&entity_key(&project_hash, &user_id))
+                    .map(User::from)
+                    .map(|r| history_page(&p, &r.update_logs, from_index, limit))
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get_file_history(
+        &self,
+        project_hash: String,
+        file_hash: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<UpdateLogsView> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .and_then(|p| {
+                self.files
+                    .get(&entity_key(&project_hash, &file_hash))
+                    .map(File::from)
+                    .map(|r| history_page(&p, &r.update_logs, from_index, limit))
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get_validator_history(
+        &self,
+        project_hash: String,
+        file_hash: String,
+        validator_id: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<UpdateLogsView> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .and_then(|p| {
+                self.validators
+                    .get(&entity_key(
+                        &project_hash,
+                        &validator_entity_id(&file_hash, &validator_id),
+                    ))
+                    .map(Validator::from)
+                    .map(|r| history_page(&p, &r.update_logs, from_index, limit))
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get_supplier_history(
+        &self,
+        project_hash: String,
+        supplier_email: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<UpdateLogsView> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .and_then(|p| {
+                self.suppliers
+                    .get(&entity_key(&project_hash, &supplier_email))
+                    .map(Supplier::from)
+                    .map(|r| history_page(&p, &r.update_logs, from_index, limit))
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get_batch_history(
+        &self,
+        project_hash: String,
+        merkle_root: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<UpdateLogsView> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .and_then(|p| {
+                self.batches
+                    .get(&entity_key(&project_hash, &merkle_root))
+                    .map(Batch::from)
+                    .map(|r| history_page(&p, &r.update_logs, from_index, limit))
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get_project_log_head(&self, project_hash: String) -> Option<LogHead> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .map(|p| LogHead {
+                length: p.audit_log.len().into(),
+                head: p.log_head,
+            })
+    }
+
+    /// The project's whole audit log in chain order, for export and
+    /// `verify_project_log`.
+    pub fn get_project_log(
+        &self,
+        project_hash: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<UpdateLogsView> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .map(|p| {
+                paginate(p.audit_log.iter(), from_index, limit)
+                    .iter()
+                    .map(|r| r.to_view())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Recomputes the chain over exported `entries`, starting from the
+    /// project hash, or from `prev_hash` when checking a later page of the
+    /// log: pass the `head` the previous page verified to. Indexes in the
+    /// result count from the first of `entries`. Reads no state, so it can
+    /// be run offline.
+    pub fn verify_project_log(
+        &self,
+        project_hash: String,
+        entries: Vec<UpdateLogsView>,
+        prev_hash: Option<String>,
+    ) -> LogChainVerification {
+        verify_chain(&prev_hash.unwrap_or(project_hash), entries)
+    }
+
+    pub fn get_project(&self, project_hash: String) -> Option<ProjectView> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .map(|p| ProjectView {
+                folder_count: p.folder_ids.len(),
+                user_count: p.user_ids.len(),
+                file_count: p.file_ids.len(),
+                supplier_count: p.supplier_ids.len(),
+                batch_count: p.batch_ids.len(),
+                created: p
+                    .update_logs
+                    .first()
+                    .and_then(|&i| p.audit_log.get(i))
+                    .map(|r| r.to_view()),
+                log_length: p.audit_log.len().into(),
+                legal_hold: p.legal_hold.clone(),
+                created_by: p.created_by,
+                project_hash: p.project_hash,
+                log_head: p.log_head,
+            })
+    }
+
+    /// Top-level folders with their sub folders nested underneath, and the
+    /// number of files and anchored batches directly in each.
+    pub fn get_folder_tree(&self, project_hash: String) -> Vec<FolderTreeNode> {
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(p) => {
+                let folders: Vec<Folder> = p
+                    .folder_ids
+                    .iter()
+                    .filter_map(|id| {
+                        self.folders
+                            .get(&entity_key(&project_hash, &id))
+                            .map(Folder::from)
+                    })
+                    .collect();
+
+                // Folders whose parent is missing are shown at the top.
+                let mut children: HashMap<Option<String>, Vec<Folder>> = HashMap::new();
+                for folder in folders {
+                    let parent_id = folder
+                        .parent_id
+                        .clone()
+                        .filter(|r| p.folder_ids.contains(r));
+                    children.entry(parent_id).or_default().push(folder);
+                }
+
+                let counts: HashMap<String, (u64, u64)> = p
+                    .folder_ids
+                    .iter()
+                    .map(|id| {
+                        let key = entity_key(&project_hash, &id);
+                        let file_count = self.folder_files.get(&key).map_or(0, |r| r.len());
+                        let batch_count = self.folder_batches.get(&key).map_or(0, |r| r.len());
+                        (id, (file_count, batch_count))
+                    })
+                    .collect();
+
+                folder_tree(None, &mut children, &counts)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    pub fn get_files(
+        &self,
+        project_hash: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<FileView> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .map(|p| {
+                paginate(p.file_ids.iter(), from_index, limit)
+                    .iter()
+                    .filter_map(|id| {
+                        self.files
+                            .get(&entity_key(&project_hash, id))
+                            .map(|r| self.file_view(r.into()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Files placed directly in `folder_hash`, not in its sub folders.
+    pub fn list_files_in_folder(
+        &self,
+        project_hash: String,
+        folder_hash: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<FileView> {
+        self.folder_files
+            .get(&entity_key(&project_hash, &folder_hash))
+            .map(|r| {
+                paginate(r.iter(), from_index, limit)
+                    .iter()
+                    .filter_map(|id| {
+                        self.files
+                            .get(&entity_key(&project_hash, id))
+                            .map(|r| self.file_view(r.into()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Current versions that haven't expired yet but will within `days`,
+    /// soonest first. Archived files are left out. Reads only the files on
+    /// the page, through the project's expiry index.
+    pub fn get_files_expiring(
+        &self,
+        project_hash: String,
+        days: u32,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<FileView> {
+        let now = env::block_timestamp();
+        let until = now.saturating_add((days as u64).saturating_mul(NANOS_PER_DAY));
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(p) => {
+                let window = p.expiring_file_ids.range((
+                    Bound::Included((now.saturating_add(1), String::new())),
+                    Bound::Excluded((until.saturating_add(1), String::new())),
+                ));
+
+                paginate(window, from_index, limit)
+                    .into_iter()
+                    .filter_map(|((_, id), _)| {
+                        self.files
+                            .get(&entity_key(&project_hash, &id))
+                            .map(|r| self.file_view(r.into()))
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Every version of the file `file_hash` belongs to, oldest first.
+    pub fn get_file_versions(&self, project_hash: String, file_hash: String) -> Vec<FileView> {
+        let get = |file_hash: &str| {
+            self.files
+                .get(&entity_key(&project_hash, file_hash))
+                .map(File::from)
+        };
+
+        let mut versions = Vec::new();
+        let mut current = get(&file_hash);
+        while let Some(file) = current {
+            current = file.previous_version.as_deref().and_then(get);
+            versions.push(file);
+        }
+        versions.reverse();
+
+        let mut current = versions
+            .last()
+            .and_then(|r| r.superseded_by.as_deref())
+            .and_then(get);
+        while let Some(file) = current {
+            current = file.superseded_by.as_deref().and_then(get);
+            versions.push(file);
+        }
+
+        versions.into_iter().map(|r| self.file_view(r)).collect()
+    }
+
+    pub fn get_file(&self, project_hash: String, file_hash: String) -> Option<FileView> {
+        self.files
+            .get(&entity_key(&project_hash, &file_hash))
+            .map(|r| self.file_view(r.into()))
+    }
+
+    pub fn get_users(
+        &self,
+        project_hash: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<User> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .map(|p| {
+                paginate(p.user_ids.iter(), from_index, limit)
+                    .iter()
+                    .filter_map(|id| {
+                        self.users
+                            .get(&entity_key(&project_hash, id))
+                            .map(User::from)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get_validators(&self, project_hash: String, file_hash: String) -> Vec<Validator> {
+        self.get_file(project_hash.to_string(), file_hash.to_string())
+            .map(|r| {
+                r.file
+                    .validators
+                    .iter()
+                    .filter_map(|id| {
+                        self.validators
+                            .get(&entity_key(
+                                &project_hash,
+                                &validator_entity_id(&file_hash, id),
+                            ))
+                            .map(Validator::from)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Every project `file_hash` has been added to, with when it was
+    /// anchored there, its status and the validators that have reported on
+    /// it. Empty if the hash was never anchored.
+    pub fn verify_document(&self, file_hash: String) -> Vec<DocumentRecord> {
+        self.file_projects
+            .get(&file_hash)
+            .map(|r| {
+                r.iter()
+                    .filter_map(|project_hash| {
+                        let p = self.projects.get(&project_hash).map(Project::from)?;
+                        let file = self
+                            .files
+                            .get(&entity_key(&project_hash, &file_hash))
+                            .map(File::from)?;
+
+                        let anchored = file
+                            .update_logs
+                            .first()
+                            .and_then(|&i| p.audit_log.get(i))
+                            .map(|r| r.to_view());
+                        let attestations = self
+                            .get_validators(project_hash.to_string(), file_hash.to_string())
+                            .into_iter()
+                            .filter(|r| r.file_status.is_some() && r.attested_by_self())
+                            .collect();
+
+                        Some(DocumentRecord {
+                            project_hash,
+                            anchored,
+                            file: self.file_view(file),
+                            attestations,
+                            legal_hold: p.legal_hold,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// `verify_document` for a document added with `add_file_commitment`,
+    /// by its hash and the salt it was committed with.
+    pub fn verify_commitment(&self, file_hash: String, salt: String) -> Vec<DocumentRecord> {
+        self.verify_document(file_commitment(&salt, &file_hash))
+            .into_iter()
+            .filter(|r| r.file.file.committed)
+            .collect()
+    }
+
+    pub fn get_batches(
+        &self,
+        project_hash: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<Batch> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .map(|p| {
+                paginate(p.batch_ids.iter(), from_index, limit)
+                    .iter()
+                    .filter_map(|id| {
+                        self.batches
+                            .get(&entity_key(&project_hash, id))
+                            .map(Batch::from)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Whether `proof` leads from the document hash `leaf` to `merkle_root`
+    /// and that root was anchored, and every project it was anchored in.
+    /// Anyone can build a tree of their own, so a proof against a root that
+    /// was never anchored isn't valid.
+    pub fn verify_inclusion(
+        &self,
+        merkle_root: String,
+        leaf: String,
+        proof: Vec<MerkleProofStep>,
+    ) -> InclusionVerification {
+        let batches: Vec<BatchRecord> = self
+            .batch_projects
+            .get(&merkle_root)
+            .map(|r| {
+                r.iter()
+                    .filter_map(|project_hash| {
+                        let p = self.projects.get(&project_hash).map(Project::from)?;
+                        let batch = self
+                            .batches
+                            .get(&entity_key(&project_hash, &merkle_root))
+                            .map(Batch::from)?;
+
+                        Some(BatchRecord {
+                            anchored: batch
+                                .update_logs
+                                .first()
+                                .and_then(|&i| p.audit_log.get(i))
+                                .map(|r| r.to_view()),
+                            project_hash,
+                            batch,
+                            legal_hold: p.legal_hold,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        InclusionVerification {
+            valid: !batches.is_empty() && merkle_root_from_proof(&leaf, &proof) == merkle_root,
+            batches,
+        }
+    }
+
+    pub fn get_suppliers(
+        &self,
+        project_hash: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<Supplier> {
+        self.projects
+            .get(&project_hash)
+            .map(Project::from)
+            .map(|p| {
+                paginate(p.supplier_ids.iter(), from_index, limit)
+                    .iter()
+                    .filter_map(|id| {
+                        self.suppliers
+                            .get(&entity_key(&project_hash, id))
+                            .map(Supplier::from)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Hash `add_project` gives a project created by `created_by`.
+    pub fn derive_project_id(
+        &self,
+        created_by: String,
+        project_name: String,
+        logo: String,
+    ) -> String {
+        project_id(&created_by, &project_name, &logo)
+    }
+
+    /// Hash of the folder currently named `folder_name` under `parent_id`,
+    /// or at the top level when that's `None`.
+    pub fn get_folder_id(
+        &self,
+        project_hash: String,
+        parent_id: Option<String>,
+        folder_name: String,
+    ) -> Option<String> {
+        self.folder_names.get(&folder_name_key(
+            &project_hash,
+            parent_id.as_deref(),
+            &folder_name,
+        ))
+    }
+
+    pub fn query_project(&self, project_hash: String) -> ReturnMessage {
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(_) => ReturnMessage {
+                code: ResultCode::Ok,
+                message: "Project found".to_owned(),
+                anchor: None,
+            },
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_supplier(
+        &mut self,
+        project_hash: String,
+        category: String,
+        contact_name: String,
+        supplier_id: String,
+        supplier_email: String,
+        company_name: String,
+        company_website: String,
+        requested_documents: String,
+    ) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+        let initial_storage = env::storage_usage();
+
+        if self.paused {
+            return ReturnMessage::paused();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => {
+                if p.legal_hold.is_some() {
+                    return ReturnMessage::legal_hold();
+                }
+
+                if !self.can(&p, &caller, Permission::ManageSuppliers) {
+                    return ReturnMessage::unauthorized();
+                }
+
+                let key = entity_key(&project_hash, &supplier_email);
+
+                match self.suppliers.get(&key).map(Supplier::from) {
+                    Some(_) => ReturnMessage {
+                        code: ResultCode::DuplicateSupplier,
+                        message: "Supplier with the same email already exists".to_owned(),
+                        anchor: None,
+                    },
+                    None => {
+                        let anchor = self.next_anchor();
+                        let log_index = p.link(
+                            &supplier_email,
+                            UpdateLogs::new(anchor.clone(), TransactionType::AddSupplier),
+                        );
+                        self.suppliers.insert(
+                            &key,
+                            &Supplier::create_supplier(
+                                category,
+                                contact_name,
+                                supplier_id,
+                                supplier_email.to_string(),
+                                company_name,
+                                company_website,
+                                requested_documents,
+                                log_index,
+                            )
+                            .into(),
+                        );
+                        p.supplier_ids.insert(&supplier_email);
+
+                        self.projects.insert(&project_hash, &p.into());
+                        self.settle_storage(&caller, initial_storage);
+
+                        ReturnMessage {
+                            code: ResultCode::Ok,
+                            message: "Supplier added successfully".to_owned(),
+                            anchor: Some(anchor),
+                        }
+                    }
+                }
+            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    /// Adds each of `folders`, at the top level or under its `parent`.
+    /// A folder can be the parent of those after it, by its index in
+    /// `folders`. See `apply_batch` for what `atomic` does.
+    pub fn add_folders(
+        &mut self,
+        project_hash: String,
+        project_id: String,
+        folders: Vec<FolderItem>,
+        atomic: bool,
+    ) -> Vec<FolderReturnMessage> {
+        let mut hashes: HashMap<usize, String> = HashMap::new();
+
+        self.apply_batch(folders, atomic, |contract, i, r| {
+            let parent_id = match r.parent {
+                Some(FolderParent::Hash(hash)) => Some(hash),
+                Some(FolderParent::Item(index)) => match hashes.get(&index) {
+                    Some(hash) => Some(hash.to_string()),
+                    None => {
+                        return ReturnMessage {
+                            code: ResultCode::FolderNotFound,
+                            message: format!("Item {} didn't add a folder before this one", index),
+                            anchor: None,
+                        }
+                        .into()
+                    }
+                },
+                None => None,
+            };
+
+            let result = FolderReturnMessage::new(
+                &project_hash,
+                contract.insert_folder(
+                    &project_hash,
+                    project_id.to_string(),
+                    parent_id,
+                    r.folder_name,
+                ),
+            );
+            if let Some(hash) = &result.hash {
+                hashes.insert(i, hash.to_string());
+            }
+
+            result
+        })
+    }
+
+    pub fn add_users(
+        &mut self,
+        project_hash: String,
+        users: Vec<UserItem>,
+        atomic: bool,
+    ) -> Vec<ReturnMessage> {
+        self.apply_batch(users, atomic, |contract, _, r| {
+            contract.add_user(project_hash.to_string(), r.user_name, r.user_id, r.role)
+        })
+    }
+
+    pub fn add_files(
+        &mut self,
+        project_hash: String,
+        files: Vec<FileItem>,
+        atomic: bool,
+    ) -> Vec<ReturnMessage> {
+        self.apply_batch(files, atomic, |contract, _, r| {
+            contract.add_file(
+                project_hash.to_string(),
+                r.file_hash,
+                r.title,
+                r.user_id,
+                r.folder_id,
+                r.expires_at,
+            )
+        })
+    }
+
+    pub fn add_validators(
+        &mut self,
+        project_hash: String,
+        validators: Vec<ValidatorItem>,
+        atomic: bool,
+    ) -> Vec<ReturnMessage> {
+        self.apply_batch(validators, atomic, |contract, _, r| {
+            contract.add_validator(
+                project_hash.to_string(),
+                r.file_hash,
+                r.validator_id,
+                r.validator_ip,
+                r.validator_email,
+                r.validator_organization,
+                r.can_sign,
+            )
+        })
+    }
+
+    /// Rewrites the v1 state, an untagged `Contract` holding one `projects`
+    /// map of whole `Project` blobs, into the current layout. v1 maps can't be
+    /// enumerated on chain, so the project hashes are passed in. Projects that
+    /// don't fit in one call can be moved afterwards with `migrate_projects`.
+    /// v1 had no owner, so one is given here; it starts as the only admin.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate(owner_id: String, project_hashes: Vec<String>) -> Self {
+        let mut legacy: ContractV1 =
+            env::state_read().unwrap_or_else(|| env::panic_str("No v1 state to migrate"));
+
+        let mut contract = Self::create(owner_id, Vec::new(), 1);
+        contract.migrate_v1_projects(&mut legacy.projects, project_hashes);
+
+        contract
+    }
+
+    /// Moves more v1 projects after `migrate`. Returns how many were moved.
+    #[private]
+    pub fn migrate_projects(&mut self, project_hashes: Vec<String>) -> u64 {
+        let mut legacy = LookupMap::new(LEGACY_PROJECTS_PREFIX);
+        self.migrate_v1_projects(&mut legacy, project_hashes)
+    }
+
+    pub fn get_state_version(&self) -> u16 {
+        self.version
+    }
+
+    /// Called by `deploy_upgrade` on the new code, in the same batch as the
+    /// deploy, so a failing migration rolls the deploy back. Each version
+    /// reads the layout of the version it replaces here; this one has nothing
+    /// to convert yet. The staged upgrade is only cleared once this succeeds.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate_state() -> Self {
+        let mut contract: Self =
+            env::state_read().unwrap_or_else(|| env::panic_str("Contract is not initialized"));
+
+        if contract.version != STATE_VERSION {
+            env::panic_str(&format!(
+                "Can't migrate state version {} to {}",
+                contract.version, STATE_VERSION
+            ));
+        }
+
+        contract.staged_upgrade = None;
+        env::storage_remove(STAGED_CODE_KEY);
+
+        contract
+    }
+
+    /// Approves adding `account_id` as an admin. See `approve_change`.
+    pub fn add_admin(&mut self, account_id: String) -> ReturnMessage {
+        self.approve_change(GovernanceChange::AddAdmin { account_id })
+    }
+
+    /// Approves removing an admin. The owner can't be removed, and the
+    /// approval threshold has to be lowered first if it would become
+    /// unreachable. See `approve_change`.
+    pub fn remove_admin(&mut self, account_id: String) -> ReturnMessage {
+        self.approve_change(GovernanceChange::RemoveAdmin { account_id })
+    }
+
+    /// Approves a new approval threshold. See `approve_change`.
+    pub fn set_approval_threshold(&mut self, approval_threshold: u32) -> ReturnMessage {
+        self.approve_change(GovernanceChange::SetApprovalThreshold { approval_threshold })
+    }
+
+    /// Stages new contract code, passed as the raw call input rather than as
+    /// JSON. Replaces any staged code and its approvals; the staging admin's
+    /// approval is counted.
+    pub fn stage_upgrade(&mut self) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+
+        if !self.is_admin(&caller) {
+            return ReturnMessage::not_admin();
+        }
+
+        let code = env::input().unwrap_or_default();
+        let code_hash = digest_bytes(&code);
+
+        let anchor = self.next_anchor();
+        env::storage_write(STAGED_CODE_KEY, &code);
+        self.staged_upgrade = Some(StagedUpgrade {
+            code_hash: code_hash.to_string(),
+            staged_by: caller.to_string(),
+            staged: anchor.clone(),
+            approvals: vec![caller],
+        });
+        emit_contract_event("upgrade_staged", &anchor);
+
+        ReturnMessage {
+            code: ResultCode::Ok,
+            message: format!("Upgrade {} staged successfully", code_hash),
+            anchor: Some(anchor),
+        }
+    }
+
+    /// Approves the staged code. `code_hash` must match it, so an approval
+    /// can't carry over to code staged afterwards.
+    pub fn approve_upgrade(&mut self, code_hash: String) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+
+        if !self.is_admin(&caller) {
+            return ReturnMessage::not_admin();
+        }
+
+        match self.staged_upgrade.take() {
+            Some(mut upgrade) => {
+                if upgrade.code_hash != code_hash {
+                    self.staged_upgrade = Some(upgrade);
+                    return ReturnMessage {
+                        code: ResultCode::CodeHashMismatch,
+                        message: "Code hash doesn't match the staged upgrade".to_owned(),
+                        anchor: None,
+                    };
+                }
+
+                if upgrade.approvals.contains(&caller) {
+                    self.staged_upgrade = Some(upgrade);
+                    return ReturnMessage {
+                        code: ResultCode::Unchanged,
+                        message: "Upgrade is already approved by this admin".to_owned(),
+                        anchor: None,
+                    };
+                }
+
+                let anchor = self.next_anchor();
+                upgrade.approvals.push(caller);
+                self.staged_upgrade = Some(upgrade);
+                emit_contract_event("upgrade_approved", &anchor);
+
+                ReturnMessage {
+                    code: ResultCode::Ok,
+                    message: "Upgrade approved successfully".to_owned(),
+                    anchor: Some(anchor),
+                }
+            }
+            None => ReturnMessage::no_staged_upgrade(),
+        }
+    }
+
+    /// Deploys the staged code to this account once enough current admins
+    /// have approved it, then calls `migrate_state` on the new code.
+    pub fn deploy_upgrade(&mut self) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+
+        if !self.is_admin(&caller) {
+            return ReturnMessage::not_admin();
+        }
+
+        match self.staged_upgrade.take() {
+            Some(upgrade) => {
+                let approvals = upgrade
+                    .approvals
+                    .iter()
+                    .filter(|r| self.is_admin(r))
+                    .count() as u32;
+
+                if approvals < self.approval_threshold {
+                    let message = format!(
+                        "Upgrade has {} of {} approvals",
+                        approvals, self.approval_threshold
+                    );
+                    self.staged_upgrade = Some(upgrade);
+                    return ReturnMessage {
+                        code: ResultCode::InsufficientApprovals,
+                        message,
+                        anchor: None,
+                    };
+                }
+
+                let code = env::storage_read(STAGED_CODE_KEY).unwrap_or_default();
+                let code_hash = upgrade.code_hash.to_string();
+                self.staged_upgrade = Some(upgrade);
+
+                let anchor = self.next_anchor();
+                emit_contract_event("upgrade_deployed", &anchor);
+                Promise::new(env::current_account_id())
+                    .deploy_contract(code)
+                    .function_call("migrate_state".to_owned(), Vec::new(), 0, MIGRATE_GAS);
+
+                ReturnMessage {
+                    code: ResultCode::Ok,
+                    message: format!("Upgrade {} deployed", code_hash),
+                    anchor: Some(anchor),
+                }
+            }
+            None => ReturnMessage::no_staged_upgrade(),
+        }
+    }
+
+    /// Blocks every mutating method except the admin ones until `unpause`.
+    pub fn pause(&mut self) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+
+        if !self.is_admin(&caller) {
+            return ReturnMessage::not_admin();
+        }
+
+        if self.paused {
+            return ReturnMessage {
+                code: ResultCode::Unchanged,
+                message: "Contract is already paused".to_owned(),
+                anchor: None,
+            };
+        }
+
+        let anchor = self.next_anchor();
+        self.paused = true;
+        emit_contract_event("contract_paused", &anchor);
+
+        ReturnMessage {
+            code: ResultCode::Ok,
+            message: "Contract paused successfully".to_owned(),
+            anchor: Some(anchor),
+        }
+    }
+
+    pub fn unpause(&mut self) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+
+        if !self.is_admin(&caller) {
+            return ReturnMessage::not_admin();
+        }
+
+        if !self.paused {
+            return ReturnMessage {
+                code: ResultCode::Unchanged,
+                message: "Contract is not paused".to_owned(),
+                anchor: None,
+            };
+        }
+
+        let anchor = self.next_anchor();
+        self.paused = false;
+        emit_contract_event("contract_unpaused", &anchor);
+
+        ReturnMessage {
+            code: ResultCode::Ok,
+            message: "Contract unpaused successfully".to_owned(),
+            anchor: Some(anchor),
+        }
+    }
+
+    /// Sets how long before its expiry a `Green` file reads as `Amber`, in
+    /// nanoseconds.
+    pub fn set_expiry_warning_period(&mut self, period: U64) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+
+        if !self.is_admin(&caller) {
+            return ReturnMessage::not_admin();
+        }
+
+        if self.expiry_warning_period == period.0 {
+            return ReturnMessage {
+                code: ResultCode::Unchanged,
+                message: "Expiry warning period is already set to this value".to_owned(),
+                anchor: None,
+            };
+        }
+
+        let anchor = self.next_anchor();
+        self.expiry_warning_period = period.0;
+        emit_contract_event("expiry_warning_period_changed", &anchor);
+
+        ReturnMessage {
+            code: ResultCode::Ok,
+            message: "Expiry warning period set successfully".to_owned(),
+            anchor: Some(anchor),
+        }
+    }
+
+    /// Freezes the project: every project method refuses with
+    /// `ResultCode::LegalHold` until the hold is released.
+    pub fn place_legal_hold(&mut self, project_hash: String, reason: String) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+
+        if !self.is_admin(&caller) {
+            return ReturnMessage::not_admin();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
             Some(mut p) => {
-                let log_message = format!("Project found : {:?}", p);
-                env::log(log_message.as_bytes());
+                if p.legal_hold.is_some() {
+                    return ReturnMessage {
+                        code: ResultCode::Unchanged,
+                        message: "Project is already under legal hold".to_owned(),
+                        anchor: None,
+                    };
+                }
+
+                let anchor = self.next_anchor();
+                let log_index = p.link(
+                    &project_hash,
+                    UpdateLogs::change(
+                        anchor.clone(),
+                        TransactionType::PlaceLegalHold,
+                        "",
+                        &reason,
+                    ),
+                );
+                p.update_logs.push(log_index);
+                p.legal_hold = Some(LegalHold {
+                    reason,
+                    placed: anchor.clone(),
+                });
+
+                self.projects.insert(&project_hash, &p.into());
+
+                ReturnMessage {
+                    code: ResultCode::Ok,
+                    message: "Legal hold placed successfully".to_owned(),
+                    anchor: Some(anchor),
+                }
+            }
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    pub fn release_legal_hold(&mut self, project_hash: String) -> ReturnMessage {
+        let caller = env::predecessor_account_id().to_string();
+
+        if !self.is_admin(&caller) {
+            return ReturnMessage::not_admin();
+        }
+
+        match self.projects.get(&project_hash).map(Project::from) {
+            Some(mut p) => match p.legal_hold.take() {
+                Some(legal_hold) => {
+                    let anchor = self.next_anchor();
+                    let log_index = p.link(
+                        &project_hash,
+                        UpdateLogs::change(
+                            anchor.clone(),
+                            TransactionType::ReleaseLegalHold,
+                            &legal_hold.reason,
+                            "",
+                        ),
+                    );
+                    p.update_logs.push(log_index);
+
+                    self.projects.insert(&project_hash, &p.into());
+
+                    ReturnMessage {
+                        code: ResultCode::Ok,
+                        message: "Legal hold released successfully".to_owned(),
+                        anchor: Some(anchor),
+                    }
+                }
+                None => ReturnMessage {
+                    code: ResultCode::Unchanged,
+                    message: "Project is not under legal hold".to_owned(),
+                    anchor: None,
+                },
+            },
+            None => ReturnMessage {
+                code: ResultCode::ProjectNotFound,
+                message: "Project not found".to_owned(),
+                anchor: None,
+            },
+        }
+    }
+
+    pub fn get_governance(&self) -> GovernanceView {
+        GovernanceView {
+            owner_id: self.owner_id.to_string(),
+            admins: self.admins.to_vec(),
+            approval_threshold: self.approval_threshold,
+            staged_upgrade: self.staged_upgrade.clone(),
+            pending_changes: self.pending_changes.clone(),
+            paused: self.paused,
+            expiry_warning_period: self.expiry_warning_period.into(),
+        }
+    }
+}
+
+/// NEP-145 storage management. Registering costs the storage account's own
+/// bytes; anything deposited beyond that pays for project writes.
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        self.assert_not_paused();
+
+        let amount = env::attached_deposit();
+        let account_id = account_id
+            .unwrap_or_else(env::predecessor_account_id)
+            .to_string();
+        let registration_only = registration_only.unwrap_or(false);
+        let min_balance = self.storage_balance_bounds().min.0;
+
+        let (account, refund) = match self.storage_accounts.get(&account_id) {
+            Some(mut account) => {
+                if registration_only {
+                    (account, amount)
+                } else {
+                    account.deposit += amount;
+                    (account, 0)
+                }
+            }
+            None => {
+                if amount < min_balance {
+                    env::panic_str("The attached deposit is less than the minimum storage balance");
+                }
+
+                let deposit = if registration_only {
+                    min_balance
+                } else {
+                    amount
+                };
+                let account = StorageAccount {
+                    deposit,
+                    used_bytes: self.account_storage_bytes,
+                };
+                (account, amount - deposit)
+            }
+        };
+
+        self.storage_accounts.insert(&account_id, &account);
+
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        account.to_balance()
+    }
+
+    /// Withdraws `amount`, or everything available, to the caller. Requires
+    /// exactly 1 yoctoNEAR attached.
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        self.assert_not_paused();
+
+        let account_id = env::predecessor_account_id();
+        let mut account = self
+            .storage_accounts
+            .get(&account_id.to_string())
+            .unwrap_or_else(|| env::panic_str(&format!("{} is not registered", account_id)));
+
+        let available = account.available();
+        let amount = amount.map_or(available, |r| r.0);
+
+        if amount > available {
+            env::panic_str("The amount is greater than the available storage balance");
+        }
+
+        account.deposit -= amount;
+        self.storage_accounts
+            .insert(&account_id.to_string(), &account);
+
+        if amount > 0 {
+            Promise::new(account_id).transfer(amount);
+        }
+
+        account.to_balance()
+    }
+
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_accounts
+            .get(&account_id.to_string())
+            .map(|r| r.to_balance())
+    }
+
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: (self.account_storage_bytes as Balance * env::storage_byte_cost()).into(),
+            max: None,
+        }
+    }
+}
+
+/// Stable, machine-readable outcome of a call. Clients should branch on the
+/// code; `message` is for humans and may be reworded. `Unchanged` means the
+/// call succeeded but there was nothing to write.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, BorshDeserialize, BorshSerialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ResultCode {
+    Ok,
+    Unchanged,
+    Unauthorized,
+    ProjectNotFound,
+    DuplicateProject,
+    DuplicateFolder,
+    DuplicateSubFolder,
+    UserNotFound,
+    DuplicateUser,
+    FileNotFound,
+    DuplicateFile,
+    ValidatorNotFound,
+    DuplicateSupplier,
+    IllegalStatusTransition,
+    InvalidValidationResult,
+    DuplicateValidator,
+    DuplicateAdmin,
+    AdminNotFound,
+    InvalidThreshold,
+    NoStagedUpgrade,
+    CodeHashMismatch,
+    InsufficientApprovals,
+    ContractPaused,
+    LegalHold,
+    FolderNotFound,
+    FolderNotEmpty,
+    InvalidFolderMove,
+    TooManyItems,
+    UserRevoked,
+    FileSuperseded,
+    InvalidExpiry,
+    InvalidCommitment,
+    InvalidMerkleRoot,
+    DuplicateBatch,
+    AwaitingApprovals,
+    InsufficientStorage,
+}
+
+impl ResultCode {
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self,
+            ResultCode::Ok | ResultCode::Unchanged | ResultCode::AwaitingApprovals
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReturnMessage {
+    pub code: ResultCode,
+    pub message: String,
+    pub anchor: Option<Anchor>,
+}
+
+impl ReturnMessage {
+    pub fn illegal_transition(from: FileStatus, to: FileStatus) -> Self {
+        Self {
+            code: ResultCode::IllegalStatusTransition,
+            message: format!("File status can't change from {} to {}", from, to),
+            anchor: None,
+        }
+    }
+
+    pub fn unauthorized() -> Self {
+        Self {
+            code: ResultCode::Unauthorized,
+            message: "Caller is not authorized for this project".to_owned(),
+            anchor: None,
+        }
+    }
+
+    pub fn not_admin() -> Self {
+        Self {
+            code: ResultCode::Unauthorized,
+            message: "Caller is not a contract admin".to_owned(),
+            anchor: None,
+        }
+    }
+
+    pub fn folder_not_found() -> Self {
+        Self {
+            code: ResultCode::FolderNotFound,
+            message: "Folder not found".to_owned(),
+            anchor: None,
+        }
+    }
+
+    pub fn duplicate_folder_name() -> Self {
+        Self {
+            code: ResultCode::DuplicateFolder,
+            message: "Folder with the same name already exists in this folder".to_owned(),
+            anchor: None,
+        }
+    }
+
+    pub fn paused() -> Self {
+        Self {
+            code: ResultCode::ContractPaused,
+            message: "Contract is paused".to_owned(),
+            anchor: None,
+        }
+    }
+
+    pub fn legal_hold() -> Self {
+        Self {
+            code: ResultCode::LegalHold,
+            message: "Project is under legal hold".to_owned(),
+            anchor: None,
+        }
+    }
+
+    pub fn no_staged_upgrade() -> Self {
+        Self {
+            code: ResultCode::NoStagedUpgrade,
+            message: "No upgrade is staged".to_owned(),
+            anchor: None,
+        }
+    }
+}
+
+impl AsRef<ReturnMessage> for ReturnMessage {
+    fn as_ref(&self) -> &ReturnMessage {
+        self
+    }
+}
+
+/// Outcome of `add_folder` and `add_sub_folder`, with the hash of the folder
+/// added.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FolderReturnMessage {
+    #[serde(flatten)]
+    pub result: ReturnMessage,
+    pub hash: Option<String>,
+}
+
+impl FolderReturnMessage {
+    fn new(project_hash: &str, result: ReturnMessage) -> Self {
+        let hash = match (&result.code, &result.anchor) {
+            (ResultCode::Ok, Some(anchor)) => Some(folder_id(project_hash, anchor.sequence.0)),
+            _ => None,
+        };
+
+        Self { result, hash }
+    }
+}
+
+impl From<ReturnMessage> for FolderReturnMessage {
+    fn from(result: ReturnMessage) -> Self {
+        Self { result, hash: None }
+    }
+}
+
+impl AsRef<ReturnMessage> for FolderReturnMessage {
+    fn as_ref(&self) -> &ReturnMessage {
+        &self.result
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProjectReturnMessage {
+    pub code: ResultCode,
+    pub message: String,
+    pub anchor: Option<Anchor>,
+    pub hash: String,
+}
+
+/// Where a write landed on chain. A verifier can look the receipt up by
+/// `block_height` and `predecessor_id` on the contract account, and
+/// `sequence` orders every write the contract has ever accepted.
+#[derive(Debug, Clone, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Anchor {
+    pub sequence: U64,
+    pub block_height: U64,
+    pub block_timestamp: U64, // nanoseconds since the unix epoch
+    pub predecessor_id: String,
+    pub signer_id: String,
+}
+
+impl Anchor {
+    pub fn new(sequence: u64) -> Self {
+        Self {
+            sequence: sequence.into(),
+            block_height: env::block_height().into(),
+            block_timestamp: env::block_timestamp().into(),
+            predecessor_id: env::predecessor_account_id().to_string(),
+            signer_id: env::signer_account_id().to_string(),
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, BorshDeserialize, BorshSerialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum TransactionType {
+    AddProject,
+    AddFolder,
+    AddSubFolder,
+    AddUser,
+    ChangeUserRole,
+    AddUserAccess,
+    RemoveUserAccess,
+    AddFile,
+    AcceptFile,
+    RejectFile,
+    UpdateFile,
+    AddValidator,
+    AddValidatorAccess,
+    RemoveValidatorAccess,
+    ValidateFile,
+    AddSupplier,
+    Migrate,
+    PlaceLegalHold,
+    ReleaseLegalHold,
+    RenameFolder,
+    MoveFolder,
+    DeleteFolder,
+    AddFileVersion,
+    SupersedeFile,
+    AddFileCommitment,
+    AnchorBatch,
+}
+
+impl TransactionType {
+    /// NEP-297 event name for entries of this type.
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            TransactionType::AddProject => "project_created",
+            TransactionType::AddFolder => "folder_added",
+            TransactionType::AddSubFolder => "sub_folder_added",
+            TransactionType::AddUser => "user_added",
+            TransactionType::ChangeUserRole => "user_role_changed",
+            TransactionType::AddUserAccess => "user_access_granted",
+            TransactionType::RemoveUserAccess => "user_access_revoked",
+            TransactionType::AddFile => "file_added",
+            TransactionType::AcceptFile
+            | TransactionType::RejectFile
+            | TransactionType::UpdateFile => "file_status_changed",
+            TransactionType::AddValidator => "validator_added",
+            TransactionType::AddValidatorAccess => "validator_access_granted",
+            TransactionType::RemoveValidatorAccess => "validator_access_revoked",
+            TransactionType::ValidateFile => "file_validated",
+            TransactionType::AddSupplier => "supplier_added",
+            TransactionType::Migrate => "entity_migrated",
+            TransactionType::PlaceLegalHold => "legal_hold_placed",
+            TransactionType::ReleaseLegalHold => "legal_hold_released",
+            TransactionType::RenameFolder => "folder_renamed",
+            TransactionType::MoveFolder => "folder_moved",
+            TransactionType::DeleteFolder => "folder_deleted",
+            TransactionType::AddFileVersion => "file_version_added",
+            TransactionType::SupersedeFile => "file_superseded",
+            TransactionType::AddFileCommitment => "file_commitment_added",
+            TransactionType::AnchorBatch => "batch_anchored",
+        }
+    }
+}
 
-                match p
-                    .suppliers
-                    .iter()
-                    .position(|r| r.supplier_email == supplier_email)
-                {
-                    Some(_index) => {
-                        return ReturnMessage {
-                            result: 409,
-                            message: "Supplier with the same email already exists".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
-                    }
-                    None => {
-                        p.suppliers.push(Supplier::create_supplier(
-                            category,
-                            contact_name,
-                            supplier_id,
-                            supplier_email,
-                            company_name,
-                            company_website,
-                            requested_documents,
-                            transaction_hash.to_string(),
-                        ));
+/// One entry of an entity's append-only history. The acting account is the
+/// anchor's `predecessor_id`; `old_value`/`new_value` describe the field the
+/// action changed and are empty for creations. `entity_id` and `prev_hash`
+/// are filled in by `Project::link`.
+#[derive(Debug, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UpdateLogs {
+    pub anchor: Anchor,
+    pub entity_id: String,
+    pub transaction_type: TransactionType,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub prev_hash: String,
+}
+
+impl UpdateLogs {
+    pub fn new(anchor: Anchor, transaction_type: TransactionType) -> Self {
+        Self {
+            anchor,
+            entity_id: "".to_owned(),
+            transaction_type,
+            old_value: None,
+            new_value: None,
+            prev_hash: "".to_owned(),
+        }
+    }
 
-                        self.projects.insert(&project_hash, &p);
+    pub fn change(
+        anchor: Anchor,
+        transaction_type: TransactionType,
+        old_value: impl ToString,
+        new_value: impl ToString,
+    ) -> Self {
+        Self {
+            anchor,
+            entity_id: "".to_owned(),
+            transaction_type,
+            old_value: Some(old_value.to_string()),
+            new_value: Some(new_value.to_string()),
+            prev_hash: "".to_owned(),
+        }
+    }
 
-                        return ReturnMessage {
-                            result: 200,
-                            message: "Folder added successfully".to_owned(),
-                            transaction_hash: transaction_hash,
-                        };
+    /// Hex sha256 over the Borsh encoding of the entry, which is, in order:
+    ///
+    /// - `anchor.sequence`, `anchor.block_height` and `anchor.block_timestamp`,
+    ///   each a little-endian u64;
+    /// - `anchor.predecessor_id`, `anchor.signer_id` and `entity_id`;
+    /// - `transaction_type`, as one byte holding its position in
+    ///   `TransactionType`, `AddProject` being 0;
+    /// - `old_value` and `new_value`, each a `0` byte when absent, or a `1`
+    ///   byte and the string;
+    /// - `prev_hash`, the previous entry's hash as a hex string.
+    ///
+    /// Strings are their UTF-8 length as a little-endian u32, then their
+    /// bytes.
+    pub fn hash(&self) -> String {
+        digest_bytes(&self.try_to_vec().unwrap())
+    }
+
+    /// Block timestamp of the anchor rendered as an ISO-8601 / RFC 3339 string.
+    pub fn time_stamp(&self) -> String {
+        Utc.timestamp_nanos(self.anchor.block_timestamp.0 as i64)
+            .to_rfc3339()
+    }
+
+    pub fn to_view(&self) -> UpdateLogsView {
+        UpdateLogsView {
+            time_stamp: self.time_stamp(),
+            actor: self.anchor.predecessor_id.clone(),
+            anchor: self.anchor.clone(),
+            entity_id: self.entity_id.clone(),
+            transaction_type: self.transaction_type,
+            old_value: self.old_value.clone(),
+            new_value: self.new_value.clone(),
+            prev_hash: self.prev_hash.clone(),
+            hash: self.hash(),
+        }
+    }
+}
+
+impl From<UpdateLogsView> for UpdateLogs {
+    fn from(view: UpdateLogsView) -> Self {
+        Self {
+            anchor: view.anchor,
+            entity_id: view.entity_id,
+            transaction_type: view.transaction_type,
+            old_value: view.old_value,
+            new_value: view.new_value,
+            prev_hash: view.prev_hash,
+        }
+    }
+}
+
+/// JSON shape of an `UpdateLogs` entry with the timestamp already formatted.
+/// `time_stamp`, `actor` and `hash` are derived and not covered by the chain.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UpdateLogsView {
+    pub time_stamp: String,
+    pub actor: String,
+    pub anchor: Anchor,
+    pub entity_id: String,
+    pub transaction_type: TransactionType,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LogHead {
+    pub head: String,
+    pub length: U64,
+}
+
+/// Outcome of replaying exported entries against the hash chain. `head` is
+/// the hash the replay ended on and should equal the on-chain `LogHead`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LogChainVerification {
+    pub valid: bool,
+    pub head: String,
+    pub length: U64,
+    pub first_invalid_index: Option<U64>,
+}
+
+/// Replays `entries` from `genesis`. An entry is invalid when its `prev_hash`
+/// doesn't match the running head or its exported `hash` doesn't match its
+/// contents, which catches dropped, reordered and edited entries.
+pub fn verify_chain(genesis: &str, entries: Vec<UpdateLogsView>) -> LogChainVerification {
+    let mut head = genesis.to_owned();
+    let mut length: u64 = 0;
+
+    for view in entries {
+        let exported_hash = view.hash.clone();
+        let update_logs = UpdateLogs::from(view);
+        let hash = update_logs.hash();
+
+        if update_logs.prev_hash != head || exported_hash != hash {
+            return LogChainVerification {
+                valid: false,
+                head,
+                length: length.into(),
+                first_invalid_index: Some(length.into()),
+            };
+        }
+
+        head = hash;
+        length += 1;
+    }
+
+    LogChainVerification {
+        valid: true,
+        head,
+        length: length.into(),
+        first_invalid_index: None,
+    }
+}
+
+/// NEP-297 envelope. Bump `EVENT_VERSION` whenever `EventData` changes shape.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a, T: Serialize> {
+    standard: &'a str,
+    version: &'a str,
+    event: &'a str,
+    data: Vec<T>,
+}
+
+/// Payload shared by every event. It only carries identifiers and the changed
+/// value; the full record can be read back through the views.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventData<'a> {
+    project_hash: &'a str,
+    entity_id: &'a str,
+    transaction_type: TransactionType,
+    actor: &'a str,
+    sequence: U64,
+    block_height: U64,
+    block_timestamp: U64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_value: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_value: Option<&'a str>,
+    log_hash: &'a str,
+}
+
+/// Payload of contract-wide events, which aren't part of any project's log.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct ContractEventData<'a> {
+    actor: &'a str,
+    sequence: U64,
+    block_height: U64,
+    block_timestamp: U64,
+}
+
+fn emit_contract_event(event: &str, anchor: &Anchor) {
+    env::log_str(&event_log(
+        event,
+        vec![ContractEventData {
+            actor: &anchor.predecessor_id,
+            sequence: anchor.sequence,
+            block_height: anchor.block_height,
+            block_timestamp: anchor.block_timestamp,
+        }],
+    ));
+}
+
+thread_local! {
+    /// Events held back since `collect_events`, as event name and `data`
+    /// entry, in the order they happened.
+    static COLLECTED_EVENTS: RefCell<Option<Vec<(&'static str, near_sdk::serde_json::Value)>>> =
+        const { RefCell::new(None) };
+}
+
+fn emit_event(project_hash: &str, update_logs: &UpdateLogs) {
+    let log_hash = update_logs.hash();
+    let event = update_logs.transaction_type.event_name();
+    let data = EventData {
+        project_hash,
+        entity_id: &update_logs.entity_id,
+        transaction_type: update_logs.transaction_type,
+        actor: &update_logs.anchor.predecessor_id,
+        sequence: update_logs.anchor.sequence,
+        block_height: update_logs.anchor.block_height,
+        block_timestamp: update_logs.anchor.block_timestamp,
+        old_value: update_logs.old_value.as_deref(),
+        new_value: update_logs.new_value.as_deref(),
+        log_hash: &log_hash,
+    };
+
+    let collected = COLLECTED_EVENTS.with(|r| match r.borrow_mut().as_mut() {
+        Some(events) => {
+            events.push((event, near_sdk::serde_json::to_value(&data).unwrap()));
+            true
+        }
+        None => false,
+    });
+
+    if !collected {
+        env::log_str(&event_log(event, vec![data]));
+    }
+}
+
+fn event_log<T: Serialize>(event: &str, data: Vec<T>) -> String {
+    let event = EventLog {
+        standard: EVENT_STANDARD,
+        version: EVENT_VERSION,
+        event,
+        data,
+    };
+
+    format!(
+        "EVENT_JSON:{}",
+        near_sdk::serde_json::to_string(&event).unwrap()
+    )
+}
+
+/// Holds back the project events of the rest of the call until
+/// `flush_events`, which logs each run of events with the same name as one
+/// NEP-297 event with several `data` entries. A receipt may only log 16 KiB,
+/// which one log per entity runs out of long before its entities do.
+fn collect_events() {
+    COLLECTED_EVENTS.with(|r| {
+        r.borrow_mut().get_or_insert_with(Vec::new);
+    });
+}
+
+/// Logs the events collected so far, see `collect_events`.
+fn collected_event_logs() -> Vec<String> {
+    COLLECTED_EVENTS.with(|r| {
+        let mut logs = Vec::new();
+        let mut run: Option<(&str, Vec<near_sdk::serde_json::Value>)> = None;
+
+        for (event, data) in r.borrow().iter().flatten() {
+            match &mut run {
+                Some((name, entries)) if name == event => entries.push(data.clone()),
+                _ => {
+                    if let Some((name, entries)) = run.take() {
+                        logs.push(event_log(name, entries));
                     }
+                    run = Some((event, vec![data.clone()]));
                 }
             }
-            None => {
-                return ReturnMessage {
-                    result: 404,
-                    message: "Project not found".to_owned(),
-                    transaction_hash: transaction_hash,
-                };
+        }
+
+        if let Some((name, entries)) = run {
+            logs.push(event_log(name, entries));
+        }
+
+        logs
+    })
+}
+
+/// Bytes the events collected so far take up once logged.
+fn collected_event_bytes() -> usize {
+    collected_event_logs().iter().map(String::len).sum()
+}
+
+/// Logs the collected events and stops collecting. Panics, reverting the
+/// call, if they don't fit in a receipt's logs, which only entities with
+/// unusually long ids or values can get to.
+fn flush_events() {
+    let logs = collected_event_logs();
+    COLLECTED_EVENTS.with(|r| r.borrow_mut().take());
+
+    if logs.iter().map(String::len).sum::<usize>() > MAX_LOG_BYTES {
+        env::panic_str(&format!(
+            "The events of this call don't fit in the {} bytes a receipt may log",
+            MAX_LOG_BYTES
+        ));
+    }
+
+    for log in logs {
+        env::log_str(&log);
+    }
+}
+
+/// Canonical id: the hex sha256 of `type_tag` followed by `parts`, each
+/// encoded as its UTF-8 length as a little-endian u32 and then its bytes.
+/// The length prefixes keep `("ab", "c")` and `("a", "bc")` apart, and the
+/// type tag keeps ids of different kinds apart.
+pub fn derive_id(type_tag: &str, parts: &[&str]) -> String {
+    let mut preimage = Vec::new();
+
+    for part in std::iter::once(type_tag).chain(parts.iter().copied()) {
+        preimage.extend_from_slice(&(part.len() as u32).to_le_bytes());
+        preimage.extend_from_slice(part.as_bytes());
+    }
+
+    digest_bytes(&preimage)
+}
+
+/// Projects are namespaced by their creator, so two accounts can use the
+/// same name and logo.
+pub fn project_id(created_by: &str, project_name: &str, logo: &str) -> String {
+    derive_id("kwiktrust:project:v1", &[created_by, project_name, logo])
+}
+
+/// Folders are told apart by the sequence number of the write that added
+/// them rather than by their name, which can change.
+pub fn folder_id(project_hash: &str, sequence: u64) -> String {
+    derive_id(
+        "kwiktrust:folder:v2",
+        &[project_hash, &sequence.to_string()],
+    )
+}
+
+/// Merkle tree leaf for the document hash `leaf`. Leaves and inner nodes
+/// have their own type tags, so neither can pass for the other.
+pub fn merkle_leaf(leaf: &str) -> String {
+    derive_id("kwiktrust:merkle_leaf:v1", &[leaf])
+}
+
+pub fn merkle_node(left: &str, right: &str) -> String {
+    derive_id("kwiktrust:merkle_node:v1", &[left, right])
+}
+
+/// Root reached by hashing `leaf` up through `proof`.
+pub fn merkle_root_from_proof(leaf: &str, proof: &[MerkleProofStep]) -> String {
+    proof
+        .iter()
+        .fold(merkle_leaf(leaf), |node, step| match step.side {
+            MerkleSide::Left => merkle_node(&step.sibling, &node),
+            MerkleSide::Right => merkle_node(&node, &step.sibling),
+        })
+}
+
+/// Entity id used in the audit log for a validator, which is only unique
+/// within its file.
+pub fn validator_entity_id(file_hash: &str, validator_id: &str) -> String {
+    format!("{}/{}", file_hash, validator_id)
+}
+
+/// Key of a folder's name among its siblings. Folder hashes never contain a
+/// `/`, so the parent and the name can't run into each other.
+pub fn folder_name_key(project_hash: &str, parent_id: Option<&str>, folder_name: &str) -> String {
+    entity_key(
+        project_hash,
+        &format!("{}/{}", parent_id.unwrap_or(""), folder_name),
+    )
+}
+
+/// Nodes for the children of `parent_id`, taken out of `children`.
+fn folder_tree(
+    parent_id: Option<&str>,
+    children: &mut HashMap<Option<String>, Vec<Folder>>,
+    counts: &HashMap<String, (u64, u64)>,
+) -> Vec<FolderTreeNode> {
+    children
+        .remove(&parent_id.map(str::to_owned))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|folder| {
+            let (file_count, batch_count) =
+                counts.get(&folder.folder_hash).copied().unwrap_or_default();
+
+            FolderTreeNode {
+                file_count,
+                batch_count,
+                children: folder_tree(Some(&folder.folder_hash), children, counts),
+                folder,
             }
+        })
+        .collect()
+}
+
+/// Why `expires_at` can't be set on a new file, if it can't.
+fn check_expiry(expires_at: Option<U64>) -> Option<ReturnMessage> {
+    match expires_at {
+        Some(expires_at) if expires_at.0 <= env::block_timestamp() => Some(ReturnMessage {
+            code: ResultCode::InvalidExpiry,
+            message: "Expiry must be in the future".to_owned(),
+            anchor: None,
+        }),
+        _ => None,
+    }
+}
+
+/// Reads the expiry dates v1 stored as free text, either RFC 3339 or a bare
+/// `YYYY-MM-DD` taken as midnight UTC. Anything else is dropped.
+fn parse_v1_expiry(expiry_date: &str) -> Option<U64> {
+    let expiry_date = expiry_date.trim();
+    let expires_at = chrono::DateTime::parse_from_rfc3339(expiry_date)
+        .map(|r| r.with_timezone(&Utc))
+        .or_else(|_| {
+            chrono::NaiveDate::parse_from_str(expiry_date, "%Y-%m-%d")
+                .map(|r| Utc.from_utc_datetime(&r.and_hms(0, 0, 0)))
+        })
+        .ok()?;
+
+    u64::try_from(expires_at.timestamp_nanos()).ok().map(U64)
+}
+
+/// What `add_file_commitment` stores for a confidential document. Built
+/// with `derive_id`, so a salt can't borrow the start of the document hash.
+pub fn file_commitment(salt: &str, file_hash: &str) -> String {
+    derive_id("kwiktrust:commitment:v1", &[salt, file_hash])
+}
+
+fn is_sha256_hex(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|r| matches!(r, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Key of a project's entity in the contract-wide collections. Project
+/// hashes are fixed-length hex, so the key can't collide across projects.
+pub fn entity_key(project_hash: &str, entity_id: &str) -> String {
+    format!("{}:{}", project_hash, entity_id)
+}
+
+fn paginate<T>(
+    items: impl Iterator<Item = T>,
+    from_index: Option<u64>,
+    limit: Option<u64>,
+) -> Vec<T> {
+    items
+        .skip(from_index.unwrap_or(0) as usize)
+        .take(limit.unwrap_or(DEFAULT_PAGE_LIMIT) as usize)
+        .collect()
+}
+
+/// Page of an entity's history starting at `from_index`, oldest first.
+/// `log_indexes` point into the project's audit log.
+fn history_page(
+    project: &Project,
+    log_indexes: &[u64],
+    from_index: Option<u64>,
+    limit: Option<u64>,
+) -> Vec<UpdateLogsView> {
+    log_indexes
+        .iter()
+        .skip(from_index.unwrap_or(0) as usize)
+        .take(limit.unwrap_or(DEFAULT_HISTORY_LIMIT) as usize)
+        .filter_map(|&i| project.audit_log.get(i))
+        .map(|r| r.to_view())
+        .collect()
+}
+
+/// Storage paid for by an account: its NEP-145 deposit and the bytes its
+/// writes, and its own registration, take up.
+#[derive(Default, BorshDeserialize, BorshSerialize)]
+pub struct StorageAccount {
+    deposit: Balance,
+    used_bytes: u64,
+}
+
+impl StorageAccount {
+    pub fn storage_cost(&self) -> Balance {
+        self.used_bytes as Balance * env::storage_byte_cost()
+    }
+
+    pub fn available(&self) -> Balance {
+        self.deposit.saturating_sub(self.storage_cost())
+    }
+
+    pub fn to_balance(&self) -> StorageBalance {
+        StorageBalance {
+            total: self.deposit.into(),
+            available: self.available().into(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
-pub struct ReturnMessage {
-    pub result: u32,
-    pub message: String,
-    pub transaction_hash: String,
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
 }
 
-#[derive(Debug, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
-pub struct ProjectReturnMessage {
-    pub result: u32,
-    pub message: String,
-    pub transaction_hash: String,
-    pub hash: String,
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
 }
 
-#[derive(Debug, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+/// Freezes a project's contents while an investigation is open. Placed and
+/// released by contract admins.
+#[derive(Debug, Clone, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
 #[serde(crate = "near_sdk::serde")]
-pub struct UpdateLogs {
-    pub time_stamp: String,
-    pub transaction_hash: String,
-    pub transaction_type: String, //TransactionType::AddUserAccess("AddUserAccess"),
+pub struct LegalHold {
+    pub reason: String,
+    pub placed: Anchor,
 }
 
-#[derive(Debug, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+/// Change to the admins or the approval threshold, applied by
+/// `approve_change`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum GovernanceChange {
+    AddAdmin { account_id: String },
+    RemoveAdmin { account_id: String },
+    SetApprovalThreshold { approval_threshold: u32 },
+}
+
+/// A `GovernanceChange` waiting for admin approval.
+#[derive(Debug, Clone, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingChange {
+    pub change: GovernanceChange,
+    pub approvals: Vec<String>,
+}
+
+/// Contract code waiting for admin approval. The wasm itself is stored
+/// under `STAGED_CODE_KEY`.
+#[derive(Debug, Clone, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StagedUpgrade {
+    pub code_hash: String,
+    pub staged_by: String,
+    pub staged: Anchor,
+    pub approvals: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
+pub struct GovernanceView {
+    pub owner_id: String,
+    pub admins: Vec<String>,
+    pub approval_threshold: u32,
+    pub staged_upgrade: Option<StagedUpgrade>,
+    pub pending_changes: Vec<PendingChange>,
+    pub paused: bool,
+    pub expiry_warning_period: U64,
+}
+
+/// Project header. Its entities live in the contract-wide collections under
+/// `entity_key`; the project only keeps their ids and the audit log.
+#[derive(BorshDeserialize, BorshSerialize)]
 pub struct Project {
     created_by: String,
     project_hash: String,
-    folders: Vec<Folder>,
-    sub_folders: Vec<SubFolder>,
-    users: Vec<User>,
-    files: Vec<File>,
-    suppliers: Vec<Supplier>,
-    update_logs: UpdateLogs,
+    folder_ids: UnorderedSet<String>,
+    user_ids: UnorderedSet<String>,
+    file_ids: UnorderedSet<String>,
+    supplier_ids: UnorderedSet<String>,
+    batch_ids: UnorderedSet<String>,
+    // Expiry and hash of the current, unarchived versions that expire.
+    expiring_file_ids: TreeMap<(u64, String), ()>,
+    audit_log: Vector<UpdateLogs>,
+    update_logs: Vec<u64>,
+    log_head: String,
+    legal_hold: Option<LegalHold>,
 }
 
 impl Project {
-    pub fn create_project(
-        created_by: String,
-        project_hash: String,
-        transaction_hash: String,
-    ) -> Self {
+    /// Empty project header whose chain starts at `project_hash`.
+    pub fn new(created_by: String, project_hash: String) -> Self {
         Self {
-            created_by,
+            log_head: project_hash.to_string(),
+            folder_ids: UnorderedSet::new(StorageKey::ProjectFolderIds {
+                project_hash: project_hash.to_string(),
+            }),
+            user_ids: UnorderedSet::new(StorageKey::ProjectUserIds {
+                project_hash: project_hash.to_string(),
+            }),
+            file_ids: UnorderedSet::new(StorageKey::ProjectFileIds {
+                project_hash: project_hash.to_string(),
+            }),
+            supplier_ids: UnorderedSet::new(StorageKey::ProjectSupplierIds {
+                project_hash: project_hash.to_string(),
+            }),
+            batch_ids: UnorderedSet::new(StorageKey::ProjectBatchIds {
+                project_hash: project_hash.to_string(),
+            }),
+            expiring_file_ids: TreeMap::new(StorageKey::ProjectExpiringFileIds {
+                project_hash: project_hash.to_string(),
+            }),
+            audit_log: Vector::new(StorageKey::ProjectAuditLog {
+                project_hash: project_hash.to_string(),
+            }),
             project_hash,
-            folders: Vec::new(),
-            sub_folders: Vec::new(),
-            files: Vec::new(),
-            suppliers: Vec::new(),
-            users: Vec::new(),
-            update_logs: UpdateLogs {
-                time_stamp: Utc::now().to_string(),
-                transaction_hash,
-                transaction_type: "Add Project".to_owned(),
-            },
+            created_by,
+            update_logs: Vec::new(),
+            legal_hold: None,
+        }
+    }
+
+    /// Creates the project header and links its `AddProject` entry. The
+    /// owner's `User` record is added by the caller.
+    pub fn create_project(created_by: String, project_hash: String, anchor: Anchor) -> Self {
+        let mut project = Self::new(created_by, project_hash);
+
+        let log_index = project.link(
+            &project.project_hash.to_string(),
+            UpdateLogs::new(anchor, TransactionType::AddProject),
+        );
+        project.update_logs.push(log_index);
+
+        project
+    }
+
+    /// Appends `update_logs` to the project's hash chain: the entry records
+    /// the current head as `prev_hash` and its own hash becomes the new head.
+    /// The chain starts from the project hash itself. Every linked entry is
+    /// also announced as an event. Returns the entry's index in the audit log.
+    pub fn link(&mut self, entity_id: &str, update_logs: UpdateLogs) -> u64 {
+        let update_logs = self.chain(entity_id, update_logs);
+        emit_event(&self.project_hash, &update_logs);
+
+        self.audit_log.push(&update_logs);
+        self.audit_log.len() - 1
+    }
+
+    /// Like `link`, without the event.
+    pub fn append(&mut self, entity_id: &str, update_logs: UpdateLogs) -> u64 {
+        let update_logs = self.chain(entity_id, update_logs);
+
+        self.audit_log.push(&update_logs);
+        self.audit_log.len() - 1
+    }
+
+    fn chain(&mut self, entity_id: &str, mut update_logs: UpdateLogs) -> UpdateLogs {
+        update_logs.entity_id = entity_id.to_owned();
+        update_logs.prev_hash = self.log_head.to_string();
+
+        self.log_head = update_logs.hash();
+
+        update_logs
+    }
+
+    /// Keeps `file` in `expiring_file_ids` while it's the current version,
+    /// isn't archived and expires.
+    pub fn index_expiry(&mut self, file: &File) {
+        if let Some(expires_at) = file.expires_at {
+            let key = (expires_at.0, file.file_hash.to_string());
+
+            if file.superseded_by.is_none() && !file.file_status.is_terminal() {
+                self.expiring_file_ids.insert(&key, &());
+            } else {
+                self.expiring_file_ids.remove(&key);
+            }
         }
     }
+
+    /// The account that created the project always holds `Role::Owner`.
+    pub fn is_owner(&self, account_id: &str) -> bool {
+        self.created_by == account_id
+    }
+}
+
+/// Summary returned by `get_project`. Folders, files, users and suppliers
+/// have views of their own.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProjectView {
+    pub created_by: String,
+    pub project_hash: String,
+    pub folder_count: u64,
+    pub user_count: u64,
+    pub file_count: u64,
+    pub supplier_count: u64,
+    pub batch_count: u64,
+    pub created: Option<UpdateLogsView>,
+    pub log_head: String,
+    pub log_length: U64,
+    pub legal_hold: Option<LegalHold>,
+}
+
+/// Where and when a document hash was anchored, returned by
+/// `verify_document`. `attestations` are the file's validators that have
+/// reported a status from their own account, revoked ones included.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DocumentRecord {
+    pub project_hash: String,
+    pub anchored: Option<UpdateLogsView>,
+    pub file: FileView,
+    pub attestations: Vec<Validator>,
+    pub legal_hold: Option<LegalHold>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FolderTreeNode {
+    pub folder: Folder,
+    pub file_count: u64,
+    pub batch_count: u64,
+    pub children: Vec<FolderTreeNode>,
 }
 
 #[derive(Debug, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
@@ -915,58 +4915,139 @@ impl Project {
 pub struct Folder {
     folder_hash: String,
     project_id: String,
+    parent_id: Option<String>, // `None` for top-level folders
     folder_name: String,
-    update_logs: UpdateLogs,
+    paid_by: Option<String>, // storage payer, `None` for data the contract paid for
+    #[serde(skip)] // read through the history views
+    update_logs: Vec<u64>,
 }
 
 impl Folder {
     pub fn create_folder(
         folder_hash: String,
         project_id: String,
+        parent_id: Option<String>,
         folder_name: String,
-        transaction_hash: String,
+        log_index: u64,
     ) -> Self {
         Self {
             folder_hash,
             project_id,
+            parent_id,
             folder_name,
-            update_logs: UpdateLogs {
-                time_stamp: Utc::now().to_string(),
-                transaction_hash,
-                transaction_type: "Add Folder".to_owned(),
-            },
+            paid_by: None,
+            update_logs: vec![log_index],
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+/// What `delete_folder` does with a folder that still has sub folders or
+/// files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
-pub struct SubFolder {
-    sub_folder_hash: String,
-    project_id: String,
-    folder_id: String,
-    sub_folder_name: String,
-    update_logs: UpdateLogs,
+pub enum FolderDeletePolicy {
+    RefuseIfNotEmpty,
+    ArchiveFiles,
 }
 
-impl SubFolder {
-    pub fn create_sub_folder(
-        sub_folder_hash: String,
-        project_id: String,
-        folder_id: String,
-        sub_folder_name: String,
-        transaction_hash: String,
-    ) -> Self {
-        Self {
-            sub_folder_hash,
-            project_id,
-            folder_id,
-            sub_folder_name,
-            update_logs: UpdateLogs {
-                time_stamp: Utc::now().to_string(),
-                transaction_hash,
-                transaction_type: "Add Sub Folder".to_owned(),
-            },
+/// Folder for `add_folders`, under `parent` or at the top level.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FolderItem {
+    pub folder_name: String,
+    pub parent: Option<FolderParent>,
+}
+
+/// Parent of a `FolderItem`: an existing folder's hash, or the index of an
+/// earlier item of the same batch, whose hash isn't known until it's added.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde", untagged)]
+pub enum FolderParent {
+    Hash(String),
+    Item(usize),
+}
+
+/// User for `add_users`, see `add_user`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UserItem {
+    pub user_name: String,
+    pub user_id: String,
+    pub role: Option<Role>,
+}
+
+/// File for `add_files`, see `add_file`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FileItem {
+    pub file_hash: String,
+    pub title: String,
+    pub user_id: String,
+    pub folder_id: String,
+    pub expires_at: Option<U64>,
+}
+
+/// Validator for `add_validators`, see `add_validator`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ValidatorItem {
+    pub file_hash: String,
+    pub validator_id: String,
+    pub validator_ip: String,
+    pub validator_email: String,
+    pub validator_organization: String,
+    pub can_sign: String,
+}
+
+/// Per-project role held by a `User`. What each role may do is defined by
+/// `Role::has_permission`.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, BorshDeserialize, BorshSerialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role {
+    Owner,
+    Admin,
+    Editor,
+    Viewer,
+    Validator,
+    Supplier,
+}
+
+/// Actions guarded by the role system. Reads are public on chain, so only
+/// mutations are listed here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ManageUsers,
+    ManageFolders,
+    AddFiles,
+    ReviewFiles,
+    ManageValidators,
+    ManageSuppliers,
+    ArchiveFiles,
+}
+
+impl Role {
+    /// Permission matrix:
+    ///
+    /// | Role      | Users | Folders | Add files | Review files | Validators | Suppliers | Archive files |
+    /// |-----------|-------|---------|-----------|--------------|------------|-----------|---------------|
+    /// | Owner     | yes   | yes     | yes       | yes          | yes        | yes       | yes           |
+    /// | Admin     | yes   | yes     | yes       | yes          | yes        | yes       | yes           |
+    /// | Editor    |       | yes     | yes       |              |            | yes       |               |
+    /// | Viewer    |       |         |           |              |            |           |               |
+    /// | Validator |       |         |           | yes          |            |           |               |
+    /// | Supplier  |       |         | yes       |              |            |           |               |
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        match self {
+            Role::Owner | Role::Admin => true,
+            Role::Editor => matches!(
+                permission,
+                Permission::ManageFolders | Permission::AddFiles | Permission::ManageSuppliers
+            ),
+            Role::Viewer => false,
+            Role::Validator => permission == Permission::ReviewFiles,
+            Role::Supplier => permission == Permission::AddFiles,
         }
     }
 }
@@ -976,21 +5057,20 @@ impl SubFolder {
 pub struct User {
     user_name: String,
     user_id: String,
+    role: Role,
     is_revoked: bool,
-    update_logs: UpdateLogs,
+    #[serde(skip)] // read through the history views
+    update_logs: Vec<u64>,
 }
 
 impl User {
-    pub fn create_user(user_name: String, user_id: String, transaction_hash: String) -> Self {
+    pub fn create_user(user_name: String, user_id: String, role: Role, log_index: u64) -> Self {
         Self {
             user_name,
             user_id,
+            role,
             is_revoked: false,
-            update_logs: UpdateLogs {
-                time_stamp: Utc::now().to_string(),
-                transaction_hash,
-                transaction_type: "Add User".to_owned(),
-            },
+            update_logs: vec![log_index],
         }
     }
 }
@@ -1002,24 +5082,104 @@ pub struct File {
     file_title: String,
     user_id: String,
     folder_id: String,
-    expiry_date: String,
-    file_status: String,
-    validators: Vec<Validator>,
+    expires_at: Option<U64>, // block timestamp in nanoseconds, `None` never expires
+    file_status: FileStatus,
+    validators: Vec<String>, // validator ids, records are read through `get_validators`
+    previous_version: Option<String>, // hash of the file this one replaced
+    superseded_by: Option<String>, // hash of the file that replaced this one
+    committed: bool,         // `file_hash` is a `file_commitment`, not the document hash
+    paid_by: Option<String>, // storage payer, `None` for data the contract paid for
+
+    //user_organization: String,
+    //user_email: String,
+    //user_ip: String,
+    //uploaded_at: String,
+    #[serde(skip)] // read through the history views
+    update_logs: Vec<u64>,
+}
+/// Review state of a `File`, serialized as `"GREEN"`, `"AMBER"`, `"RED"` and
+/// `"GREY"` like the front end always used. Files start out `Red`; `Grey`
+/// archives a file for good. See `required_permission` for the moves
+/// between them.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, BorshDeserialize, BorshSerialize,
+)]
+#[serde(crate = "near_sdk::serde", rename_all = "UPPERCASE")]
+pub enum FileStatus {
+    Green,
+    Amber,
+    Red,
+    Grey,
+}
+
+impl FileStatus {
+    /// Permission needed to move a file from this status to `next`, `None`
+    /// if the move isn't allowed:
+    ///
+    /// | From  | To    | Permission    |
+    /// |-------|-------|---------------|
+    /// | Red   | Amber | ReviewFiles   |
+    /// | Red   | Green | ReviewFiles   |
+    /// | Amber | Red   | ReviewFiles   |
+    /// | Amber | Green | ReviewFiles   |
+    /// | Green | Red   | ArchiveFiles  |
+    /// | Green | Amber | ArchiveFiles  |
+    /// | Red   | Grey  | ArchiveFiles  |
+    /// | Amber | Grey  | ArchiveFiles  |
+    /// | Green | Grey  | ArchiveFiles  |
+    ///
+    /// Reopening an accepted file is left to those who can archive it, so a
+    /// reviewer can't quietly undo another's acceptance. Nothing leaves
+    /// `Grey`.
+    pub fn required_permission(&self, next: FileStatus) -> Option<Permission> {
+        use FileStatus::*;
+
+        match (self, next) {
+            (Red, Amber) | (Red, Green) | (Amber, Red) | (Amber, Green) => {
+                Some(Permission::ReviewFiles)
+            }
+            (Green, Red) | (Green, Amber) => Some(Permission::ArchiveFiles),
+            (Red | Amber | Green, Grey) => Some(Permission::ArchiveFiles),
+            _ => None,
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        *self == FileStatus::Grey
+    }
+
+    /// Statuses a validator may report on a file. Archiving isn't a verdict.
+    pub fn is_verdict(&self) -> bool {
+        !self.is_terminal()
+    }
+}
+
+/// Parses the statuses v1 stored as plain strings.
+impl std::str::FromStr for FileStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "GREEN" => Ok(FileStatus::Green),
+            "AMBER" => Ok(FileStatus::Amber),
+            "RED" => Ok(FileStatus::Red),
+            "GREY" => Ok(FileStatus::Grey),
+            _ => Err(format!("Unknown file status {:?}", s)),
+        }
+    }
+}
 
-    //user_organization: String,
-    //user_email: String,
-    //user_ip: String,
-    //uploaded_at: String,
-    update_logs: UpdateLogs,
-}
-/*
-fileStatus = {
-    GREEN: 'GREEN',
-    AMBER: 'AMBER',
-    RED: 'RED',
-    GREY: 'GREY',
-};
-*/
+impl std::fmt::Display for FileStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let status = match self {
+            FileStatus::Green => "GREEN",
+            FileStatus::Amber => "AMBER",
+            FileStatus::Red => "RED",
+            FileStatus::Grey => "GREY",
+        };
+        write!(f, "{}", status)
+    }
+}
 
 impl File {
     pub fn create_file(
@@ -1027,26 +5187,55 @@ impl File {
         file_title: String,
         user_id: String,
         folder_id: String,
-        expiry_date: String,
-        transaction_hash: String,
+        expires_at: Option<U64>,
+        log_index: u64,
     ) -> Self {
         Self {
             file_hash,
             file_title,
             user_id,
             folder_id,
-            expiry_date,
-            file_status: "RED".to_owned(),
+            expires_at,
+            file_status: FileStatus::Red,
             validators: Vec::new(),
-            update_logs: UpdateLogs {
-                time_stamp: Utc::now().to_string(),
-                transaction_hash,
-                transaction_type: "Add File".to_owned(),
-            },
+            previous_version: None,
+            superseded_by: None,
+            committed: false,
+            paid_by: None,
+            update_logs: vec![log_index],
+        }
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|r| r.0 <= now)
+    }
+
+    /// Status as of `now`: expired files read as `Grey`, and `Green` files
+    /// as `Amber` once they expire within `warning_period`. The stored status
+    /// is left alone.
+    pub fn effective_status(&self, now: u64, warning_period: u64) -> FileStatus {
+        match self.expires_at {
+            Some(_) if self.is_expired(now) => FileStatus::Grey,
+            Some(expires_at)
+                if self.file_status == FileStatus::Green
+                    && expires_at.0 <= now.saturating_add(warning_period) =>
+            {
+                FileStatus::Amber
+            }
+            _ => self.file_status,
         }
     }
 }
 
+/// A `File` with its status as of the block the view was called in.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FileView {
+    #[serde(flatten)]
+    pub file: File,
+    pub effective_status: FileStatus,
+}
+
 #[derive(Debug, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Validator {
@@ -1056,9 +5245,10 @@ pub struct Validator {
     validator_organization: String,
     is_revoked: bool,
     can_sign: String, // Used hard coded and had purpose
-    file_validation_hash: String,
-    file_status: String,
-    update_logs: UpdateLogs,
+    file_validation_anchor: Option<Anchor>,
+    file_status: Option<FileStatus>,
+    #[serde(skip)] // read through the history views
+    update_logs: Vec<u64>,
 }
 
 impl Validator {
@@ -1068,7 +5258,7 @@ impl Validator {
         validator_email: String,
         validator_organization: String,
         can_sign: String, // Used hard coded and had purpose
-        transaction_hash: String,
+        log_index: u64,
     ) -> Self {
         Self {
             validator_id,
@@ -1077,15 +5267,20 @@ impl Validator {
             validator_organization,
             can_sign,
             is_revoked: false,
-            file_validation_hash: "".to_owned(),
-            file_status: "".to_owned(),
-            update_logs: UpdateLogs {
-                time_stamp: Utc::now().to_string(),
-                transaction_hash,
-                transaction_type: "Add Validator".to_owned(),
-            },
+            file_validation_anchor: None,
+            file_status: None,
+            update_logs: vec![log_index],
         }
     }
+
+    /// Whether the verdict was recorded by the validator's own account, not
+    /// by a reviewer on its behalf. v1 verdicts, which anyone could set, never
+    /// are.
+    pub fn attested_by_self(&self) -> bool {
+        self.file_validation_anchor
+            .as_ref()
+            .is_some_and(|r| r.predecessor_id == self.validator_id)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
@@ -1098,10 +5293,12 @@ pub struct Supplier {
     company_name: String,
     company_website: String,
     requested_documents: String,
-    update_logs: UpdateLogs,
+    #[serde(skip)] // read through the history views
+    update_logs: Vec<u64>,
 }
 
 impl Supplier {
+    #[allow(clippy::too_many_arguments)]
     pub fn create_supplier(
         category: String,
         contact_name: String,
@@ -1110,7 +5307,7 @@ impl Supplier {
         company_name: String,
         company_website: String,
         requested_documents: String,
-        transaction_hash: String,
+        log_index: u64,
     ) -> Self {
         Self {
             category,
@@ -1120,11 +5317,1269 @@ impl Supplier {
             company_name,
             company_website,
             requested_documents,
-            update_logs: UpdateLogs {
-                time_stamp: Utc::now().to_string(),
-                transaction_hash,
-                transaction_type: "Add Supplier".to_owned(),
-            },
+            update_logs: vec![log_index],
+        }
+    }
+}
+
+/// Merkle root of documents anchored together by `anchor_batch`.
+#[derive(Debug, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Batch {
+    merkle_root: String,
+    batch_title: String,
+    folder_id: String,
+    leaf_count: u64,
+    #[serde(skip)] // read through the history views
+    update_logs: Vec<u64>,
+}
+
+impl Batch {
+    pub fn create_batch(
+        merkle_root: String,
+        batch_title: String,
+        folder_id: String,
+        leaf_count: u64,
+        log_index: u64,
+    ) -> Self {
+        Self {
+            merkle_root,
+            batch_title,
+            folder_id,
+            leaf_count,
+            update_logs: vec![log_index],
+        }
+    }
+}
+
+/// Side of the path a sibling hash sits on in a `MerkleProofStep`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum MerkleSide {
+    Left,
+    Right,
+}
+
+/// One level of a Merkle proof, from the leaf up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MerkleProofStep {
+    pub sibling: String,
+    pub side: MerkleSide,
+}
+
+/// Where a Merkle root was anchored, returned by `verify_inclusion`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BatchRecord {
+    pub project_hash: String,
+    pub anchored: Option<UpdateLogsView>,
+    pub batch: Batch,
+    pub legal_hold: Option<LegalHold>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InclusionVerification {
+    pub valid: bool,
+    pub batches: Vec<BatchRecord>,
+}
+
+/// Storage envelopes of the project header and entities. A layout change
+/// adds a variant and converts older ones on read; v1 entities only ever
+/// existed inside `ProjectV1` and are converted by `migrate`.
+macro_rules! versioned {
+    ($($versioned:ident($current:ident)),* $(,)?) => {
+        $(
+            #[derive(BorshDeserialize, BorshSerialize)]
+            pub enum $versioned {
+                V2($current),
+            }
+
+            impl From<$versioned> for $current {
+                fn from(versioned: $versioned) -> Self {
+                    match versioned {
+                        $versioned::V2(current) => current,
+                    }
+                }
+            }
+
+            impl From<$current> for $versioned {
+                fn from(current: $current) -> Self {
+                    $versioned::V2(current)
+                }
+            }
+        )*
+    };
+}
+
+versioned!(
+    VersionedProject(Project),
+    VersionedFolder(Folder),
+    VersionedUser(User),
+    VersionedFile(File),
+    VersionedValidator(Validator),
+    VersionedSupplier(Supplier),
+    VersionedBatch(Batch),
+);
+
+// The v1 layout, exactly as it was deployed. Only read by `migrate` and
+// `migrate_projects`.
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV1 {
+    projects: LookupMap<String, ProjectV1>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ProjectV1 {
+    created_by: String,
+    project_hash: String,
+    folders: Vec<FolderV1>,
+    sub_folders: Vec<SubFolderV1>,
+    users: Vec<UserV1>,
+    files: Vec<FileV1>,
+    suppliers: Vec<SupplierV1>,
+    update_logs: UpdateLogsV1,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct FolderV1 {
+    folder_hash: String,
+    project_id: String,
+    folder_name: String,
+    update_logs: UpdateLogsV1,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SubFolderV1 {
+    sub_folder_hash: String,
+    project_id: String,
+    folder_id: String,
+    sub_folder_name: String,
+    update_logs: UpdateLogsV1,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct UserV1 {
+    user_name: String,
+    user_id: String,
+    is_revoked: bool,
+    update_logs: UpdateLogsV1,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct FileV1 {
+    file_hash: String,
+    file_title: String,
+    user_id: String,
+    folder_id: String,
+    expiry_date: String,
+    file_status: String,
+    validators: Vec<ValidatorV1>,
+    update_logs: UpdateLogsV1,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ValidatorV1 {
+    validator_id: String,
+    validator_ip: String,
+    validator_email: String,
+    validator_organization: String,
+    is_revoked: bool,
+    can_sign: String,
+    #[allow(dead_code)] // never set by v1
+    file_validation_hash: String,
+    file_status: String,
+    update_logs: UpdateLogsV1,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SupplierV1 {
+    category: String,
+    contact_name: String,
+    supplier_id: String,
+    supplier_email: String,
+    company_name: String,
+    company_website: String,
+    requested_documents: String,
+    update_logs: UpdateLogsV1,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct UpdateLogsV1 {
+    time_stamp: String,
+    #[allow(dead_code)] // always the "Transaction Hash" placeholder
+    transaction_hash: String,
+    transaction_type: String,
+}
+
+impl UpdateLogsV1 {
+    /// `Migrate` entry standing in for this one, e.g. with `old_value`
+    /// `"Add File at 2022-09-01 10:00:00 UTC"`.
+    pub fn migrate(self, anchor: Anchor) -> UpdateLogs {
+        UpdateLogs::change(
+            anchor,
+            TransactionType::Migrate,
+            format!("{} at {}", self.transaction_type, self.time_stamp),
+            STATE_VERSION,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn set_caller(account_id: &str) {
+        set_caller_with_deposit(account_id, 10u128.pow(25));
+    }
+
+    fn set_caller_with_deposit(account_id: &str, attached_deposit: Balance) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id.parse().unwrap())
+            .attached_deposit(attached_deposit)
+            .build());
+    }
+
+    /// A contract with a project created by `owner`, who has a storage
+    /// deposit, holding the folder "Docs". Returns the project and folder ids.
+    fn setup(owner: &str) -> (Contract, String, String) {
+        set_caller(owner);
+        let mut contract = Contract::new("owner.near".to_owned(), Vec::new(), 1);
+        contract.storage_deposit(None, None);
+        let project_hash = contract
+            .add_project("Project".to_owned(), "logo".to_owned())
+            .hash;
+        let folder_id = contract
+            .add_folder(
+                project_hash.to_string(),
+                "project".to_owned(),
+                "Docs".to_owned(),
+            )
+            .hash
+            .unwrap();
+
+        (contract, project_hash, folder_id)
+    }
+
+    fn file_item(file_hash: &str, folder_id: &str) -> FileItem {
+        FileItem {
+            file_hash: file_hash.to_owned(),
+            title: "File".to_owned(),
+            user_id: "carol.near".to_owned(),
+            folder_id: folder_id.to_owned(),
+            expires_at: None,
+        }
+    }
+
+    fn log_v1(transaction_type: &str) -> UpdateLogsV1 {
+        UpdateLogsV1 {
+            time_stamp: "2022-09-01 10:00:00 UTC".to_owned(),
+            transaction_hash: "Transaction Hash".to_owned(),
+            transaction_type: transaction_type.to_owned(),
+        }
+    }
+
+    fn user_v1(user_id: &str, is_revoked: bool) -> UserV1 {
+        UserV1 {
+            user_name: user_id.to_owned(),
+            user_id: user_id.to_owned(),
+            is_revoked,
+            update_logs: log_v1("Add User"),
+        }
+    }
+
+    fn project_v1(project_hash: &str) -> ProjectV1 {
+        ProjectV1 {
+            created_by: "carol.near".to_owned(),
+            project_hash: project_hash.to_owned(),
+            folders: vec![
+                FolderV1 {
+                    folder_hash: "docs".to_owned(),
+                    project_id: "project".to_owned(),
+                    folder_name: "Docs".to_owned(),
+                    update_logs: log_v1("Add Folder"),
+                },
+                FolderV1 {
+                    folder_hash: "other".to_owned(),
+                    project_id: "project".to_owned(),
+                    folder_name: "Other".to_owned(),
+                    update_logs: log_v1("Add Folder"),
+                },
+            ],
+            sub_folders: vec![
+                SubFolderV1 {
+                    sub_folder_hash: "drafts".to_owned(),
+                    project_id: "project".to_owned(),
+                    folder_id: "docs".to_owned(),
+                    sub_folder_name: "Drafts".to_owned(),
+                    update_logs: log_v1("Add Sub Folder"),
+                },
+                SubFolderV1 {
+                    sub_folder_hash: "orphan".to_owned(),
+                    project_id: "project".to_owned(),
+                    folder_id: "missing".to_owned(),
+                    sub_folder_name: "Orphan".to_owned(),
+                    update_logs: log_v1("Add Sub Folder"),
+                },
+            ],
+            users: vec![user_v1("dave.near", false), user_v1("erin.near", true)],
+            files: vec![FileV1 {
+                file_hash: "a".repeat(64),
+                file_title: "Contract".to_owned(),
+                user_id: "dave.near".to_owned(),
+                folder_id: "drafts".to_owned(),
+                expiry_date: "2030-01-01".to_owned(),
+                file_status: "GREEN".to_owned(),
+                validators: vec![ValidatorV1 {
+                    validator_id: "val.near".to_owned(),
+                    validator_ip: "127.0.0.1".to_owned(),
+                    validator_email: "val@example.com".to_owned(),
+                    validator_organization: "Val".to_owned(),
+                    is_revoked: false,
+                    can_sign: "true".to_owned(),
+                    file_validation_hash: String::new(),
+                    file_status: "AMBER".to_owned(),
+                    update_logs: log_v1("Add Validator"),
+                }],
+                update_logs: log_v1("Add File"),
+            }],
+            suppliers: Vec::new(),
+            update_logs: log_v1("Add Project"),
+        }
+    }
+
+    #[test]
+    fn migrate_v1_state() {
+        set_caller("owner.near");
+        let mut legacy = ContractV1 {
+            projects: LookupMap::new(LEGACY_PROJECTS_PREFIX),
+        };
+        legacy.projects.insert(&"p1".to_owned(), &project_v1("p1"));
+        legacy.projects.insert(&"p2".to_owned(), &project_v1("p2"));
+        env::state_write(&legacy);
+
+        let mut contract = Contract::migrate("owner.near".to_owned(), vec!["p1".to_owned()]);
+        assert_eq!(contract.get_state_version(), STATE_VERSION);
+        assert!(contract.get_project("p2".to_owned()).is_none());
+        assert_eq!(
+            contract.migrate_projects(vec!["p1".to_owned(), "p2".to_owned(), "p3".to_owned()]),
+            1
+        );
+        assert_eq!(contract.migrate_projects(vec!["p2".to_owned()]), 0);
+
+        let project = contract.get_project("p2".to_owned()).unwrap();
+        assert_eq!(project.created_by, "carol.near");
+        assert_eq!(project.folder_count, 4);
+        assert_eq!(project.user_count, 3);
+        assert_eq!(project.file_count, 1);
+
+        // The orphaned sub folder is shown at the top.
+        let tree = contract.get_folder_tree("p2".to_owned());
+        let mut roots: Vec<&str> = tree.iter().map(|r| r.folder.folder_hash.as_str()).collect();
+        roots.sort_unstable();
+        assert_eq!(roots, ["docs", "orphan", "other"]);
+        let docs = tree
+            .iter()
+            .find(|r| r.folder.folder_hash == "docs")
+            .unwrap();
+        assert_eq!(docs.children.len(), 1);
+        assert_eq!(docs.children[0].folder.folder_hash, "drafts");
+        assert_eq!(docs.children[0].file_count, 1);
+
+        let users = contract.get_users("p2".to_owned(), None, None);
+        let user = |id: &str| users.iter().find(|r| r.user_id == id).unwrap();
+        assert_eq!(user("carol.near").role, Role::Owner);
+        assert_eq!(user("dave.near").role, Role::Editor);
+        assert!(!user("dave.near").is_revoked);
+        assert_eq!(user("erin.near").role, Role::Editor);
+        assert!(user("erin.near").is_revoked);
+
+        let files = contract.get_files("p2".to_owned(), None, None);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].file.file_status, FileStatus::Green);
+        assert_eq!(files[0].file.folder_id, "drafts");
+        assert_eq!(
+            files[0].file.expires_at,
+            Some(U64(1_893_456_000_000_000_000))
+        );
+
+        let validators = contract.get_validators("p2".to_owned(), "a".repeat(64));
+        assert_eq!(validators.len(), 1);
+        assert_eq!(validators[0].validator_id, "val.near");
+        assert_eq!(validators[0].file_status, Some(FileStatus::Amber));
+
+        let log = contract.get_project_log("p2".to_owned(), None, None);
+        assert_eq!(log.len() as u64, project.log_length.0);
+        assert!(
+            log.iter()
+                .all(|r| r.transaction_type == TransactionType::Migrate
+                    || r.entity_id == "carol.near")
+        );
+        let verification = contract.verify_project_log("p2".to_owned(), log, None);
+        assert!(verification.valid);
+        assert_eq!(verification.head, project.log_head);
+
+        // Page by page, each from the head the one before verified to.
+        let mut head = None;
+        for from_index in (0..project.log_length.0).step_by(4) {
+            let page = contract.get_project_log("p2".to_owned(), Some(from_index), Some(4));
+            let verification = contract.verify_project_log("p2".to_owned(), page, head);
+            assert!(verification.valid);
+            head = Some(verification.head);
+        }
+        assert_eq!(head, Some(project.log_head));
+
+        let mut tampered = contract.get_project_log("p2".to_owned(), None, None);
+        tampered[1].old_value = Some("Add File at 2023-01-01 00:00:00 UTC".to_owned());
+        let verification = contract.verify_project_log("p2".to_owned(), tampered, None);
+        assert!(!verification.valid);
+        assert_eq!(verification.first_invalid_index, Some(U64(1)));
+    }
+
+    // Expected ids were computed independently, with Python's hashlib over
+    // the length-prefixed preimage.
+    #[test]
+    fn derive_id_known_answers() {
+        assert_eq!(
+            derive_id("t", &[]),
+            "dc1d91e715d2e039ab2726c46c3e9352b7859ca0783037bd089b04f666298fc3"
+        );
+        assert_eq!(
+            derive_id("t", &["ab", "c"]),
+            "054718fb822c673ec1b1d08b4221561ee40f494d28cf7ac026e4041232af2198"
+        );
+        assert_eq!(
+            derive_id("t", &["a", "bc"]),
+            "ce273633413ac486d27b9dd114626b14e3f06667e1de2f2b98b0acc55881225d"
+        );
+        assert_eq!(
+            folder_id("p", 1),
+            "9f102c7da0a08036228515a77bffe319a2085ff4e2cffca0868edae95246cf8d"
+        );
+        assert_eq!(
+            file_commitment("salt", &"a".repeat(64)),
+            "1040ffebfeb50dc5e4a25e75833956b1d9453ca467ba99fbb6ca91a1599c2d21"
+        );
+        assert_ne!(
+            file_commitment("salta", &"a".repeat(63)),
+            file_commitment("salt", &"a".repeat(64))
+        );
+    }
+
+    // Tree over the leaves "a…", "b…" and "c…", each 64 characters, with the
+    // odd leaf paired one level up. Computed independently like the ids above.
+    const LEAF_A: &str = "2fd3ecfc919504ffe565f522fd3d74245562ab7366531bd65ac6c4daa2a51694";
+    const LEAF_B: &str = "a8c4e81155bb2ceee0b2c7968df7e4f9d327e62c6a1162423e220f3059d46949";
+    const LEAF_C: &str = "a177625ca513127be157b93c1216c41027e89e1ced758eb0faf1faafd87cd0a1";
+    const NODE_AB: &str = "886e123a538959e96dc45927c1e8c470e705f52528024e82c0a1626299f977b5";
+    const ROOT: &str = "9b6be2c806e9132b73755b899afa1e4b839e9639e1261579e82e3a921e1ec807";
+
+    fn step(sibling: &str, side: MerkleSide) -> MerkleProofStep {
+        MerkleProofStep {
+            sibling: sibling.to_owned(),
+            side,
+        }
+    }
+
+    #[test]
+    fn merkle_known_answers() {
+        assert_eq!(merkle_leaf(&"a".repeat(64)), LEAF_A);
+        assert_eq!(merkle_leaf(&"b".repeat(64)), LEAF_B);
+        assert_eq!(merkle_leaf(&"c".repeat(64)), LEAF_C);
+        assert_eq!(merkle_node(LEAF_A, LEAF_B), NODE_AB);
+        assert_eq!(merkle_node(NODE_AB, LEAF_C), ROOT);
+
+        let proof_a = [
+            step(LEAF_B, MerkleSide::Right),
+            step(LEAF_C, MerkleSide::Right),
+        ];
+        assert_eq!(merkle_root_from_proof(&"a".repeat(64), &proof_a), ROOT);
+        let proof_c = [step(NODE_AB, MerkleSide::Left)];
+        assert_eq!(merkle_root_from_proof(&"c".repeat(64), &proof_c), ROOT);
+        assert_ne!(merkle_root_from_proof(&"b".repeat(64), &proof_a), ROOT);
+    }
+
+    #[test]
+    fn verify_inclusion_in_anchored_batch() {
+        let (mut contract, project_hash, folder_id) = setup("carol.near");
+        let result = contract.anchor_batch(
+            project_hash.to_string(),
+            ROOT.to_owned(),
+            "Batch".to_owned(),
+            folder_id,
+            3,
+        );
+        assert_eq!(result.code, ResultCode::Ok);
+
+        let proof = vec![step(NODE_AB, MerkleSide::Left)];
+        let verification = contract.verify_inclusion(ROOT.to_owned(), "c".repeat(64), proof);
+        assert!(verification.valid);
+        assert_eq!(verification.batches.len(), 1);
+        assert_eq!(verification.batches[0].project_hash, project_hash);
+        assert!(verification.batches[0].anchored.is_some());
+
+        let proof = vec![step(NODE_AB, MerkleSide::Right)];
+        assert!(
+            !contract
+                .verify_inclusion(ROOT.to_owned(), "c".repeat(64), proof)
+                .valid
+        );
+
+        // A sound proof against a root nobody anchored proves nothing.
+        let proof = vec![step(LEAF_B, MerkleSide::Right)];
+        let verification = contract.verify_inclusion(NODE_AB.to_owned(), "a".repeat(64), proof);
+        assert!(!verification.valid);
+        assert!(verification.batches.is_empty());
+    }
+
+    // Each id and value at its longest, logged from the longest account id.
+    #[test]
+    fn batch_at_the_cap_fits_in_the_logs() {
+        let owner = format!("{}.near", "o".repeat(59));
+        let (mut contract, project_hash, folder_id) = setup(&owner);
+        let file_hash = "a".repeat(64);
+        let mut item = file_item(&file_hash, &folder_id);
+        item.user_id = owner.to_string();
+        contract.add_files(project_hash.to_string(), vec![item], true);
+
+        set_caller(&owner);
+        let validators = (0..MAX_EVENTS_PER_CALL)
+            .map(|i| ValidatorItem {
+                file_hash: file_hash.to_string(),
+                validator_id: format!("{:0>59}.near", i),
+                validator_ip: "127.0.0.1".to_owned(),
+                validator_email: format!("{}@example.com", i),
+                validator_organization: "Org".to_owned(),
+                can_sign: "true".to_owned(),
+            })
+            .collect();
+        let results = contract.add_validators(project_hash, validators, true);
+        assert!(results.iter().all(|r| r.code == ResultCode::Ok));
+
+        let logs = near_sdk::test_utils::get_logs();
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].len() <= MAX_LOG_BYTES);
+    }
+
+    #[test]
+    fn best_effort_batch_stops_when_storage_runs_out() {
+        let (mut contract, project_hash, folder_id) = setup("carol.near");
+        contract.add_user(
+            project_hash.to_string(),
+            "Dave".to_owned(),
+            "dave.near".to_owned(),
+            None,
+        );
+
+        // Room for a few files only.
+        let min_balance = contract.storage_balance_bounds().min.0;
+        set_caller_with_deposit("dave.near", min_balance + 6_000 * env::storage_byte_cost());
+        contract.storage_deposit(None, None);
+
+        let files = (0..10)
+            .map(|i| file_item(&format!("{:x>64}", i), &folder_id))
+            .collect();
+        let results = contract.add_files(project_hash.to_string(), files, false);
+        let added = results
+            .iter()
+            .take_while(|r| r.code == ResultCode::Ok)
+            .count();
+        assert!(added > 0 && added < 10);
+        assert!(results[added..]
+            .iter()
+            .all(|r| r.code == ResultCode::InsufficientStorage));
+
+        let balance = contract
+            .storage_balance_of("dave.near".parse().unwrap())
+            .unwrap();
+        assert!(balance.available.0 < balance.total.0);
+        assert_eq!(contract.get_files(project_hash, None, None).len(), added);
+    }
+
+    #[test]
+    fn renamed_and_moved_folders_free_their_names() {
+        let (mut contract, project_hash, docs) = setup("carol.near");
+        let drafts = contract
+            .add_sub_folder(
+                project_hash.to_string(),
+                "project".to_owned(),
+                docs.to_string(),
+                "Drafts".to_owned(),
+            )
+            .hash
+            .unwrap();
+
+        let result =
+            contract.rename_folder(project_hash.to_string(), docs.to_string(), "Old".to_owned());
+        assert_eq!(result.code, ResultCode::Ok);
+        let result = contract.add_folder(
+            project_hash.to_string(),
+            "project".to_owned(),
+            "Docs".to_owned(),
+        );
+        assert_eq!(result.result.code, ResultCode::Ok);
+        let new_docs = result.hash.unwrap();
+        assert_ne!(new_docs, docs);
+        assert_eq!(
+            contract.get_folder_id(project_hash.to_string(), None, "Docs".to_owned()),
+            Some(new_docs)
+        );
+
+        let result = contract.move_folder(project_hash.to_string(), drafts.to_string(), None);
+        assert_eq!(result.code, ResultCode::Ok);
+        let result = contract.add_sub_folder(
+            project_hash.to_string(),
+            "project".to_owned(),
+            docs.to_string(),
+            "Drafts".to_owned(),
+        );
+        assert_eq!(result.result.code, ResultCode::Ok);
+        assert_ne!(result.hash.unwrap(), drafts);
+
+        let result = contract.add_folder(
+            project_hash.to_string(),
+            "project".to_owned(),
+            "Old".to_owned(),
+        );
+        assert_eq!(result.result.code, ResultCode::DuplicateFolder);
+        assert!(result.hash.is_none());
+    }
+
+    #[test]
+    fn batch_folders_can_nest_under_earlier_items() {
+        let (mut contract, project_hash, docs) = setup("carol.near");
+        let item = |folder_name: &str, parent: Option<FolderParent>| FolderItem {
+            folder_name: folder_name.to_owned(),
+            parent,
+        };
+        let results = contract.add_folders(
+            project_hash.to_string(),
+            "project".to_owned(),
+            vec![
+                item("Contracts", None),
+                item("Signed", Some(FolderParent::Item(0))),
+                item("Reports", Some(FolderParent::Hash(docs))),
+                item("Lost", Some(FolderParent::Item(5))),
+            ],
+            false,
+        );
+        let codes: Vec<ResultCode> = results.iter().map(|r| r.result.code).collect();
+        assert_eq!(
+            codes,
+            [
+                ResultCode::Ok,
+                ResultCode::Ok,
+                ResultCode::Ok,
+                ResultCode::FolderNotFound
+            ]
+        );
+
+        let tree = contract.get_folder_tree(project_hash);
+        let contracts = tree
+            .iter()
+            .find(|r| Some(&r.folder.folder_hash) == results[0].hash.as_ref())
+            .unwrap();
+        assert_eq!(contracts.children[0].folder.folder_name, "Signed");
+    }
+
+    // Computed independently from the preimage documented on
+    // `UpdateLogs::hash`.
+    #[test]
+    fn log_hash_known_answers() {
+        let anchor =
+            |sequence: u64, block_height: u64, block_timestamp: u64, account: &str| Anchor {
+                sequence: U64(sequence),
+                block_height: U64(block_height),
+                block_timestamp: U64(block_timestamp),
+                predecessor_id: account.to_owned(),
+                signer_id: account.to_owned(),
+            };
+
+        let mut update_logs = UpdateLogs::change(
+            anchor(7, 100, 1_700_000_000_000_000_000, "carol.near"),
+            TransactionType::RenameFolder,
+            "Docs",
+            "Old",
+        );
+        update_logs.entity_id = "docs".to_owned();
+        update_logs.prev_hash = "p".to_owned();
+        assert_eq!(
+            update_logs.hash(),
+            "53f2c01a3c93a907a93209262ecff9f1c8205a8047841ef6fcf4991c2f8467a9"
+        );
+
+        let mut update_logs =
+            UpdateLogs::new(anchor(1, 0, 0, "a.near"), TransactionType::AddProject);
+        update_logs.entity_id = "p".to_owned();
+        update_logs.prev_hash = "p".to_owned();
+        assert_eq!(
+            update_logs.hash(),
+            "ae552072d0bb062112cb9d6064f2269a04c95ea6e4ad7c44080ad185f16ffece"
+        );
+    }
+
+    #[test]
+    fn only_self_recorded_verdicts_are_attestations() {
+        let (mut contract, project_hash, folder_id) = setup("carol.near");
+        let file_hash = "a".repeat(64);
+        contract.add_files(
+            project_hash.to_string(),
+            vec![file_item(&file_hash, &folder_id)],
+            true,
+        );
+        for (validator_id, validator_email) in [
+            ("val.near", "val@example.com"),
+            ("eve.near", "eve@example.com"),
+        ] {
+            contract.add_validator(
+                project_hash.to_string(),
+                file_hash.to_string(),
+                validator_id.to_owned(),
+                "127.0.0.1".to_owned(),
+                validator_email.to_owned(),
+                "Org".to_owned(),
+                "true".to_owned(),
+            );
+        }
+
+        // The owner speaks for eve, val speaks for itself.
+        let result = contract.update_validator_after_file_validation(
+            project_hash.to_string(),
+            file_hash.to_string(),
+            "eve@example.com".to_owned(),
+            FileStatus::Green,
+        );
+        assert_eq!(result.code, ResultCode::Ok);
+        set_caller("val.near");
+        contract.storage_deposit(None, None);
+        let result = contract.update_validator_after_file_validation(
+            project_hash.to_string(),
+            file_hash.to_string(),
+            "val@example.com".to_owned(),
+            FileStatus::Amber,
+        );
+        assert_eq!(result.code, ResultCode::Ok);
+
+        // Nor can val, a stranger to the project, speak for eve.
+        let result = contract.update_validator_after_file_validation(
+            project_hash.to_string(),
+            file_hash.to_string(),
+            "eve@example.com".to_owned(),
+            FileStatus::Red,
+        );
+        assert_eq!(result.code, ResultCode::Unauthorized);
+
+        let records = contract.verify_document(file_hash);
+        let attestations: Vec<&str> = records[0]
+            .attestations
+            .iter()
+            .map(|r| r.validator_id.as_str())
+            .collect();
+        assert_eq!(attestations, ["val.near"]);
+    }
+
+    #[test]
+    fn expiry_index_follows_versions_and_archiving() {
+        let (mut contract, project_hash, folder_id) = setup("carol.near");
+        let expiring = |file_hash: &str, days: u64| FileItem {
+            expires_at: Some(U64(days * NANOS_PER_DAY)),
+            ..file_item(file_hash, &folder_id)
+        };
+        contract.add_files(
+            project_hash.to_string(),
+            vec![
+                expiring(&"a".repeat(64), 3),
+                expiring(&"b".repeat(64), 1),
+                file_item(&"c".repeat(64), &folder_id),
+                expiring(&"d".repeat(64), 10),
+            ],
+            true,
+        );
+        let result = contract.add_file_version(
+            project_hash.to_string(),
+            "b".repeat(64),
+            "e".repeat(64),
+            "File".to_owned(),
+            "carol.near".to_owned(),
+            None,
+            Some(U64(2 * NANOS_PER_DAY)),
+            false,
+        );
+        assert_eq!(result.code, ResultCode::Ok);
+
+        let expiring_within = |contract: &Contract, from_index: Option<u64>, limit: Option<u64>| {
+            contract
+                .get_files_expiring(project_hash.to_string(), 5, from_index, limit)
+                .into_iter()
+                .map(|r| r.file.file_hash.chars().next().unwrap())
+                .collect::<String>()
+        };
+        assert_eq!(expiring_within(&contract, None, None), "ea");
+        assert_eq!(expiring_within(&contract, Some(1), Some(1)), "a");
+
+        let result =
+            contract.update_file(project_hash.to_string(), "a".repeat(64), FileStatus::Grey);
+        assert_eq!(result.code, ResultCode::Ok);
+        assert_eq!(expiring_within(&contract, None, None), "e");
+    }
+
+    fn user(contract: &mut Contract, project_hash: &str, user_id: &str, role: Role) {
+        let result = contract.add_user(
+            project_hash.to_owned(),
+            user_id.to_owned(),
+            user_id.to_owned(),
+            Some(role),
+        );
+        assert_eq!(result.code, ResultCode::Ok);
+    }
+
+    fn available(contract: &Contract, account_id: &str) -> Balance {
+        contract
+            .storage_balance_of(account_id.parse().unwrap())
+            .unwrap()
+            .available
+            .0
+    }
+
+    #[test]
+    fn roles_decide_who_may_write() {
+        let (mut contract, project_hash, folder_id) = setup("carol.near");
+        for account_id in ["dave.near", "erin.near"] {
+            set_caller(account_id);
+            contract.storage_deposit(None, None);
         }
+
+        // A stranger to the project can't touch it.
+        set_caller("dave.near");
+        let result = contract.add_folder(
+            project_hash.to_string(),
+            "project".to_owned(),
+            "Dave".to_owned(),
+        );
+        assert_eq!(result.result.code, ResultCode::Unauthorized);
+        let results = contract.add_files(
+            project_hash.to_string(),
+            vec![file_item(&"a".repeat(64), &folder_id)],
+            false,
+        );
+        assert_eq!(results[0].code, ResultCode::Unauthorized);
+
+        set_caller("carol.near");
+        user(&mut contract, &project_hash, "dave.near", Role::Viewer);
+        user(&mut contract, &project_hash, "erin.near", Role::Admin);
+
+        set_caller("dave.near");
+        let result = contract.add_folder(
+            project_hash.to_string(),
+            "project".to_owned(),
+            "Dave".to_owned(),
+        );
+        assert_eq!(result.result.code, ResultCode::Unauthorized);
+
+        // Admins manage users, but only the owner hands out admin.
+        set_caller("erin.near");
+        let result = contract.add_user(
+            project_hash.to_string(),
+            "frank.near".to_owned(),
+            "frank.near".to_owned(),
+            Some(Role::Admin),
+        );
+        assert_eq!(result.code, ResultCode::Unauthorized);
+        let result = contract.change_user_role(
+            project_hash.to_string(),
+            "dave.near".to_owned(),
+            Role::Editor,
+        );
+        assert_eq!(result.code, ResultCode::Ok);
+
+        set_caller("dave.near");
+        let result = contract.add_folder(
+            project_hash.to_string(),
+            "project".to_owned(),
+            "Dave".to_owned(),
+        );
+        assert_eq!(result.result.code, ResultCode::Ok);
+        let result = contract.add_user(
+            project_hash.to_string(),
+            "frank.near".to_owned(),
+            "frank.near".to_owned(),
+            None,
+        );
+        assert_eq!(result.code, ResultCode::Unauthorized);
+
+        // Revoked users are strangers again.
+        set_caller("erin.near");
+        let result = contract.remove_user_access(project_hash.to_string(), "dave.near".to_owned());
+        assert_eq!(result.code, ResultCode::Ok);
+        set_caller("dave.near");
+        let result = contract.add_folder(
+            project_hash.to_string(),
+            "project".to_owned(),
+            "Dave 2".to_owned(),
+        );
+        assert_eq!(result.result.code, ResultCode::Unauthorized);
+    }
+
+    #[test]
+    fn status_transitions_follow_the_table() {
+        let (mut contract, project_hash, folder_id) = setup("carol.near");
+        let file_hash = "a".repeat(64);
+        contract.add_files(
+            project_hash.to_string(),
+            vec![file_item(&file_hash, &folder_id)],
+            true,
+        );
+        user(&mut contract, &project_hash, "val.near", Role::Validator);
+        set_caller("val.near");
+        contract.storage_deposit(None, None);
+
+        let result = contract.accept_file(project_hash.to_string(), file_hash.to_string());
+        assert_eq!(result.code, ResultCode::Ok);
+        let result = contract.accept_file(project_hash.to_string(), file_hash.to_string());
+        assert_eq!(result.code, ResultCode::Unchanged);
+
+        // Reopening an accepted file takes ArchiveFiles.
+        let result = contract.reject_file(project_hash.to_string(), file_hash.to_string());
+        assert_eq!(result.code, ResultCode::Unauthorized);
+        let result = contract.update_file(
+            project_hash.to_string(),
+            file_hash.to_string(),
+            FileStatus::Grey,
+        );
+        assert_eq!(result.code, ResultCode::Unauthorized);
+
+        set_caller("carol.near");
+        let result = contract.update_file(
+            project_hash.to_string(),
+            file_hash.to_string(),
+            FileStatus::Grey,
+        );
+        assert_eq!(result.code, ResultCode::Ok);
+        let result = contract.update_file(
+            project_hash.to_string(),
+            file_hash.to_string(),
+            FileStatus::Red,
+        );
+        assert_eq!(result.code, ResultCode::IllegalStatusTransition);
+        assert_eq!(
+            contract
+                .get_file(project_hash.to_string(), file_hash)
+                .unwrap()
+                .file
+                .file_status,
+            FileStatus::Grey
+        );
+    }
+
+    #[test]
+    fn storage_is_charged_to_the_writer_and_refunded_to_the_payer() {
+        let (mut contract, project_hash, _) = setup("carol.near");
+        user(&mut contract, &project_hash, "dave.near", Role::Editor);
+        let min_balance = contract.storage_balance_bounds().min.0;
+        set_caller_with_deposit("dave.near", min_balance + 10_000 * env::storage_byte_cost());
+        contract.storage_deposit(None, None);
+        let deposited = available(&contract, "dave.near");
+
+        let folder_id = contract
+            .add_folder(
+                project_hash.to_string(),
+                "project".to_owned(),
+                "Dave".to_owned(),
+            )
+            .hash
+            .unwrap();
+        let after_add = available(&contract, "dave.near");
+        assert!(after_add < deposited);
+
+        // Carol deletes the folder, and pays for the log entry, but the
+        // folder's bytes go back to dave.
+        set_caller("carol.near");
+        let carol_before = available(&contract, "carol.near");
+        let result = contract.delete_folder(
+            project_hash.to_string(),
+            folder_id,
+            FolderDeletePolicy::RefuseIfNotEmpty,
+        );
+        assert_eq!(result.code, ResultCode::Ok);
+        let after_delete = available(&contract, "dave.near");
+        assert!(after_add < after_delete && after_delete <= deposited);
+        assert!(available(&contract, "carol.near") < carol_before);
+
+        set_caller_with_deposit("dave.near", 1);
+        let balance = contract.storage_withdraw(None);
+        assert_eq!(balance.available.0, 0);
+        assert_eq!(
+            balance.total.0,
+            min_balance + 10_000 * env::storage_byte_cost() - after_delete
+        );
+    }
+
+    #[test]
+    fn pause_and_legal_hold_refuse_writes() {
+        let (mut contract, project_hash, folder_id) = setup("carol.near");
+        let file_hash = "a".repeat(64);
+        contract.add_files(
+            project_hash.to_string(),
+            vec![file_item(&file_hash, &folder_id)],
+            true,
+        );
+
+        let result = contract.pause();
+        assert_eq!(result.code, ResultCode::Unauthorized);
+        set_caller("owner.near");
+        assert_eq!(contract.pause().code, ResultCode::Ok);
+        set_caller("carol.near");
+        let result = contract.accept_file(project_hash.to_string(), file_hash.to_string());
+        assert_eq!(result.code, ResultCode::ContractPaused);
+        set_caller("owner.near");
+        assert_eq!(contract.unpause().code, ResultCode::Ok);
+
+        let result = contract.place_legal_hold(project_hash.to_string(), "Audit".to_owned());
+        assert_eq!(result.code, ResultCode::Ok);
+        set_caller("carol.near");
+        let result = contract.accept_file(project_hash.to_string(), file_hash.to_string());
+        assert_eq!(result.code, ResultCode::LegalHold);
+        let result = contract.delete_folder(
+            project_hash.to_string(),
+            folder_id.to_string(),
+            FolderDeletePolicy::ArchiveFiles,
+        );
+        assert_eq!(result.code, ResultCode::LegalHold);
+        let records = contract.verify_document(file_hash.to_string());
+        assert_eq!(records[0].legal_hold.as_ref().unwrap().reason, "Audit");
+
+        set_caller("owner.near");
+        let result = contract.release_legal_hold(project_hash.to_string());
+        assert_eq!(result.code, ResultCode::Ok);
+        set_caller("carol.near");
+        let result = contract.accept_file(project_hash.to_string(), file_hash);
+        assert_eq!(result.code, ResultCode::Ok);
+    }
+
+    #[test]
+    fn deleting_a_folder_archives_its_files_only_when_asked() {
+        let (mut contract, project_hash, folder_id) = setup("carol.near");
+        let sub_folder_id = contract
+            .add_sub_folder(
+                project_hash.to_string(),
+                "project".to_owned(),
+                folder_id.to_string(),
+                "Drafts".to_owned(),
+            )
+            .hash
+            .unwrap();
+        let file_hash = "a".repeat(64);
+        contract.add_files(
+            project_hash.to_string(),
+            vec![file_item(&file_hash, &sub_folder_id)],
+            true,
+        );
+
+        let result = contract.delete_folder(
+            project_hash.to_string(),
+            folder_id.to_string(),
+            FolderDeletePolicy::RefuseIfNotEmpty,
+        );
+        assert_eq!(result.code, ResultCode::FolderNotEmpty);
+        let result = contract.delete_folder(
+            project_hash.to_string(),
+            folder_id,
+            FolderDeletePolicy::ArchiveFiles,
+        );
+        assert_eq!(result.code, ResultCode::Ok);
+
+        assert!(contract
+            .get_folder_tree(project_hash.to_string())
+            .is_empty());
+        let file = contract.get_file(project_hash, file_hash).unwrap();
+        assert_eq!(file.file.file_status, FileStatus::Grey);
+    }
+
+    #[test]
+    fn files_only_go_in_project_folders_for_uploaders_who_may_add_them() {
+        let (mut contract, project_hash, folder_id) = setup("carol.near");
+        user(&mut contract, &project_hash, "dave.near", Role::Viewer);
+        user(&mut contract, &project_hash, "erin.near", Role::Supplier);
+        let result = contract.remove_user_access(project_hash.to_string(), "erin.near".to_owned());
+        assert_eq!(result.code, ResultCode::Ok);
+
+        let add = |contract: &mut Contract, folder_id: &str, user_id: &str| {
+            contract
+                .add_file(
+                    project_hash.to_string(),
+                    "a".repeat(64),
+                    "File".to_owned(),
+                    user_id.to_owned(),
+                    folder_id.to_owned(),
+                    None,
+                )
+                .code
+        };
+        assert_eq!(
+            add(&mut contract, &"f".repeat(64), "carol.near"),
+            ResultCode::FolderNotFound
+        );
+        assert_eq!(
+            add(&mut contract, &folder_id, "frank.near"),
+            ResultCode::UserNotFound
+        );
+        assert_eq!(
+            add(&mut contract, &folder_id, "erin.near"),
+            ResultCode::UserRevoked
+        );
+        assert_eq!(
+            add(&mut contract, &folder_id, "dave.near"),
+            ResultCode::Unauthorized
+        );
+        assert_eq!(add(&mut contract, &folder_id, "carol.near"), ResultCode::Ok);
+
+        let files = contract.list_files_in_folder(project_hash.to_string(), folder_id, None, None);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].file.file_hash, "a".repeat(64));
+    }
+
+    #[test]
+    fn versions_supersede_and_carry_validators_without_results() {
+        let (mut contract, project_hash, folder_id) = setup("carol.near");
+        let (first, second) = ("a".repeat(64), "b".repeat(64));
+        contract.add_files(
+            project_hash.to_string(),
+            vec![file_item(&first, &folder_id)],
+            true,
+        );
+        contract.add_validator(
+            project_hash.to_string(),
+            first.to_string(),
+            "val.near".to_owned(),
+            "127.0.0.1".to_owned(),
+            "val@example.com".to_owned(),
+            "Org".to_owned(),
+            "true".to_owned(),
+        );
+        let result = contract.update_validator_after_file_validation(
+            project_hash.to_string(),
+            first.to_string(),
+            "val@example.com".to_owned(),
+            FileStatus::Green,
+        );
+        assert_eq!(result.code, ResultCode::Ok);
+
+        let add_version = |contract: &mut Contract, previous: &str, file_hash: &str| {
+            contract
+                .add_file_version(
+                    project_hash.to_string(),
+                    previous.to_owned(),
+                    file_hash.to_owned(),
+                    "File".to_owned(),
+                    "carol.near".to_owned(),
+                    None,
+                    None,
+                    true,
+                )
+                .code
+        };
+        assert_eq!(add_version(&mut contract, &first, &second), ResultCode::Ok);
+        assert_eq!(
+            add_version(&mut contract, &first, &"c".repeat(64)),
+            ResultCode::FileSuperseded
+        );
+
+        let versions = contract.get_file_versions(project_hash.to_string(), second.to_string());
+        let hashes: Vec<&str> = versions.iter().map(|r| r.file.file_hash.as_str()).collect();
+        assert_eq!(hashes, [first.as_str(), second.as_str()]);
+        assert_eq!(
+            versions[0].file.superseded_by.as_deref(),
+            Some(second.as_str())
+        );
+
+        let validators = contract.get_validators(project_hash.to_string(), second.to_string());
+        assert_eq!(validators.len(), 1);
+        assert_eq!(validators[0].validator_id, "val.near");
+        assert_eq!(validators[0].file_status, None);
+    }
+
+    #[test]
+    fn verify_document_shows_where_a_hash_was_anchored() {
+        let (mut contract, project_hash, folder_id) = setup("carol.near");
+        let file_hash = "a".repeat(64);
+        contract.add_files(
+            project_hash.to_string(),
+            vec![file_item(&file_hash, &folder_id)],
+            true,
+        );
+        assert!(contract.verify_document("b".repeat(64)).is_empty());
+
+        let records = contract.verify_document(file_hash.to_string());
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].project_hash, project_hash);
+        let anchored = records[0].anchored.as_ref().unwrap();
+        assert_eq!(anchored.entity_id, file_hash);
+        assert_eq!(anchored.actor, "carol.near");
+        assert_eq!(records[0].file.effective_status, FileStatus::Red);
+        assert!(records[0].attestations.is_empty());
+    }
+
+    #[test]
+    fn commitments_hide_the_document_hash() {
+        let (mut contract, project_hash, folder_id) = setup("carol.near");
+        let file_hash = "a".repeat(64);
+        let add = |contract: &mut Contract, commitment: String| {
+            contract
+                .add_file_commitment(
+                    project_hash.to_string(),
+                    commitment,
+                    "File".to_owned(),
+                    "carol.near".to_owned(),
+                    folder_id.to_string(),
+                    None,
+                )
+                .code
+        };
+        assert_eq!(
+            add(&mut contract, "not a digest".to_owned()),
+            ResultCode::InvalidCommitment
+        );
+        assert_eq!(
+            add(&mut contract, file_commitment("salt", &file_hash)),
+            ResultCode::Ok
+        );
+
+        assert!(contract.verify_document(file_hash.to_string()).is_empty());
+        assert!(contract
+            .verify_commitment(file_hash.to_string(), "pepper".to_owned())
+            .is_empty());
+        let records = contract.verify_commitment(file_hash, "salt".to_owned());
+        assert_eq!(records.len(), 1);
+        assert!(records[0].file.file.committed);
+    }
+
+    #[test]
+    fn best_effort_batch_reports_each_item() {
+        let (mut contract, project_hash, folder_id) = setup("carol.near");
+        let results = contract.add_files(
+            project_hash.to_string(),
+            vec![
+                file_item(&"a".repeat(64), &folder_id),
+                file_item(&"a".repeat(64), &folder_id),
+                file_item(&"b".repeat(64), &"f".repeat(64)),
+                file_item(&"c".repeat(64), &folder_id),
+            ],
+            false,
+        );
+        let codes: Vec<ResultCode> = results.into_iter().map(|r| r.code).collect();
+        assert_eq!(
+            codes,
+            [
+                ResultCode::Ok,
+                ResultCode::DuplicateFile,
+                ResultCode::FolderNotFound,
+                ResultCode::Ok
+            ]
+        );
+        assert_eq!(contract.get_files(project_hash, None, None).len(), 2);
     }
 }